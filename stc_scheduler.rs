@@ -1,15 +1,26 @@
 use dashmap::DashMap;
-use parking_lot::{Mutex, RwLock};
+use parking_lot::{MappedRwLockWriteGuard, Mutex, RwLock, RwLockWriteGuard};
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fs;
-use std::time::Instant;
-use tracing::{info, warn};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering as AtomicOrdering};
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::{debug, error, info, warn};
 
 use crate::lib::stc;
+
+pub mod metrics;
 use stc::{AdminRequestPayload, OffloadRequestPayload, ServerCommand};
 use stc::server_command::{CommandType as ServerCmdType, Payload as ServerPayload};
 
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+const DEFAULT_SHARD_CONTAINER: &str = "Programming";
+const DEFAULT_CONTAINER_FILE: &str = "containers.json";
+const ADMIN_ACTIONS: [&str; 3] = ["quarantine", "unquarantine", "evict"];
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum NodeTier {
     Offline = 0,
     Tier3Mobile = 1,
@@ -17,7 +28,7 @@ pub enum NodeTier {
     Tier1HighPerformance = 3,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum HealthState {
     Healthy,
     Degraded,
@@ -34,6 +45,70 @@ pub struct ContainerInfo {
     pub description: String,
     pub status: String,
     pub required_vram_gb: u32,
+    // 이중화를 위해 최소 몇 개 노드에 동시에 떠 있어야 하는지 (0/1 = 단일 배치)
+    #[serde(default)]
+    pub min_replicas: u32,
+    #[serde(default)]
+    pub required_accelerator: AcceleratorKind,
+    // 콜드 스타트 시 디스크에서 읽어야 하는 모델 크기
+    #[serde(default)]
+    pub model_size_gb: f64,
+    // 통신량이 많은 컨테이너 id (같은 zone 배치 선호, 강제 아님)
+    #[serde(default)]
+    pub communicates_with: Vec<String>,
+    #[serde(default)]
+    pub sla: SlaClass,
+    #[serde(default)]
+    pub resource_profile: ResourceProfile,
+}
+
+// 배치 엄격도. Standard는 기존 필터 그대로
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SlaClass {
+    BestEffort, // Degraded 허용, 복제본 최소치 무시 (단일 배치)
+    #[default]
+    Standard, // Degraded 허용, min_replicas 준수
+    Premium,    // Healthy + RTT 상한 + 복제본 최소치 보장
+}
+
+// 작업이 주로 쓰는 자원. 부하 계산 시 해당 자원의 사용률에 가중치를 둔다
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResourceProfile {
+    #[default]
+    Combined, // 기존 방식 (config.load_blend)
+    CpuBound,
+    GpuBound,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AcceleratorKind {
+    #[default]
+    Any, // 이식 가능한 컨테이너 (CPU 포함 어디서나)
+    Cuda,
+    Rocm,
+    Npu,
+    IntelArc,
+}
+
+impl ContainerInfo {
+    pub fn is_gpu_bound(&self) -> bool {
+        self.required_accelerator != AcceleratorKind::Any || self.required_vram_gb > 0
+    }
+}
+
+impl AcceleratorKind {
+    pub fn is_supported_by(&self, node: &NodeContext) -> bool {
+        match self {
+            AcceleratorKind::Any => true,
+            AcceleratorKind::Cuda => node.has_cuda,
+            AcceleratorKind::Rocm => node.has_rocm,
+            AcceleratorKind::Npu => node.has_npu,
+            AcceleratorKind::IntelArc => node.has_intel_arc,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -57,12 +132,135 @@ pub struct NodeContext {
     pub is_charging: bool,
     pub network_type: String,
     pub user_allowed: bool,
+    pub zone: Option<String>,               // 장애 도메인 (가용 영역)
+    pub cost_per_hour: Option<f64>,         // 온프레미스 등 비용 정보가 없으면 None
+    pub storage_speed_mbps: f64,            // 모델 로딩 디스크 속도 (0 = 모름)
+    pub cached_models: Vec<String>,         // 이미 로컬에 캐시된 모델/컨테이너 id
+    pub advertised_models: HashSet<String>, // 서빙 가능한 모델/도메인 태그 (비어 있으면 제한 없음)
 
     // ==== OPI 3.0 / 네트워크 / 셀프힐링 ====
     pub net_rtt_ema_ms: f64,     // Heartbeat 기반 RTT EMA
     pub health_state: HealthState,
-    pub failure_count: u32,      // 연속 실패/타임아웃 횟수
-    pub is_quarantined: bool,    // 스케줄링 대상 제외 여부
+    pub failure_count: u32,             // 연속 실패/타임아웃 횟수
+    pub is_quarantined: bool,           // 스케줄링 대상 제외 여부
+    pub capability_invalid: bool,       // 하드웨어 값으로 OPI 계산 불가 (NaN/inf)
+    pub total_vram_gb: u32,             // GPU 메모리 (0 = 전용 VRAM 없음)
+    pub healthy_uptime_secs: f64,       // 누적 정상 가동 시간 (격리 시 0으로 리셋)
+    pub failure_domain: Option<String>, // 같이 죽을 수 있는 단위 (호스트/랙/서브넷 태그)
+    pub is_draining: bool,              // 운영자 유지보수: 새 작업만 받지 않음 (헬스와 무관)
+    pub pending_reservations: u32,      // 배정됐지만 아직 부하에 반영되지 않은 Offload/Shard 수
+    pub reserved_at: Option<Instant>,   // 마지막 예약 시각 (reservation_ttl_secs 기준)
+    pub network_kind: NetworkKind,      // network_type을 등록/갱신 시 파싱한 값
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NetworkKind {
+    Ethernet,
+    Wifi,
+    Cellular,
+    #[default]
+    Unknown,
+}
+
+impl NetworkKind {
+    // 에이전트마다 표기가 달라서 흔한 별칭을 같이 받는다
+    pub fn parse(network_type: &str) -> Self {
+        match network_type.trim().to_ascii_lowercase().as_str() {
+            "ethernet" | "eth" | "lan" | "wired" => NetworkKind::Ethernet,
+            "wifi" | "wi-fi" | "wlan" => NetworkKind::Wifi,
+            "cellular" | "mobile" | "lte" | "5g" | "4g" | "3g" => NetworkKind::Cellular,
+            _ => NetworkKind::Unknown,
+        }
+    }
+}
+
+// 네트워크 종류별 effective OPI 나눗수 (순간 RTT와 별개로 링크 자체의 불안정성 반영, 1.0 미만은 1.0)
+#[derive(Debug, Clone, Copy)]
+pub struct NetworkPenalties {
+    pub ethernet: f64,
+    pub wifi: f64,
+    pub cellular: f64,
+    pub unknown: f64,
+}
+
+impl Default for NetworkPenalties {
+    fn default() -> Self {
+        Self {
+            ethernet: 1.0,
+            wifi: 1.3,
+            cellular: 3.0,
+            unknown: 1.0,
+        }
+    }
+}
+
+impl NetworkPenalties {
+    pub fn for_kind(&self, kind: NetworkKind) -> f64 {
+        match kind {
+            NetworkKind::Ethernet => self.ethernet,
+            NetworkKind::Wifi => self.wifi,
+            NetworkKind::Cellular => self.cellular,
+            NetworkKind::Unknown => self.unknown,
+        }
+    }
+}
+
+impl NodeContext {
+    // GPU를 열거하지 못했거나 없는 노드: CPU 워크로드 전용으로 취급
+    pub fn is_cpu_only(&self) -> bool {
+        self.compute_units == 0
+    }
+
+    // 태그를 광고하지 않은 구버전 에이전트는 기존처럼 하드웨어 조건만 본다
+    pub fn can_serve(&self, container: &ContainerInfo) -> bool {
+        if self.advertised_models.is_empty() {
+            return true;
+        }
+        self.advertised_models.contains(&container.domain)
+            || container
+                .ai_models
+                .iter()
+                .any(|m| self.advertised_models.contains(m))
+    }
+}
+
+// calculate_raw_opi 항목별 가중치. Default는 기존 고정 상수와 동일
+#[derive(Debug, Clone, Copy)]
+pub struct OpiWeights {
+    pub ram_per_gb: f64,
+    pub bandwidth_divisor: f64, // GB/s를 이 값으로 나눔
+    pub pcie_per_lane_gen: f64,
+    pub compute_per_unit: f64,
+    // 가속기 배수: 여러 개를 가진 노드는 가장 큰 배수 하나만 적용 (중복 곱셈 방지)
+    pub rocm_multiplier: f64,
+    pub cuda_multiplier: f64,
+    pub intel_arc_multiplier: f64,
+    pub npu_multiplier: f64,
+    // 항목별 상한: 전체 OPI 중 한 항목이 차지할 수 있는 최대 비율 (None = 제한 없음)
+    pub ram_cap: Option<f64>,
+    pub bandwidth_cap: Option<f64>,
+    pub pcie_cap: Option<f64>,
+    pub compute_cap: Option<f64>,
+}
+
+impl Default for OpiWeights {
+    fn default() -> Self {
+        Self {
+            ram_per_gb: 5.0,
+            bandwidth_divisor: 10.0,
+            pcie_per_lane_gen: 2.0,
+            compute_per_unit: 0.5,
+            rocm_multiplier: 1.1,
+            cuda_multiplier: 1.3,
+            intel_arc_multiplier: 1.1,
+            npu_multiplier: 1.05,
+            ram_cap: None,
+            bandwidth_cap: None,
+            pcie_cap: None,
+            compute_cap: None,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -72,338 +270,8012 @@ pub struct OverloadThresholds {
     pub vram_pressure_max: f64,
 }
 
+// Heartbeat 기반 헬스 판정 기준 (고지연 위성 링크 등은 degraded_rtt_ms를 올려서 사용)
+#[derive(Debug, Clone, Copy)]
+pub struct HealthThresholds {
+    pub suspect_after_secs: f64,
+    pub quarantine_after_secs: f64,
+    pub degraded_rtt_ms: f64,
+    // 연속 명령 실패가 이 횟수에 도달하면 RTT와 무관하게 격리
+    pub max_consecutive_failures: u32,
+}
+
+impl Default for HealthThresholds {
+    fn default() -> Self {
+        Self {
+            suspect_after_secs: 10.0,
+            quarantine_after_secs: 30.0,
+            degraded_rtt_ms: 150.0,
+            max_consecutive_failures: 3,
+        }
+    }
+}
+
 #[derive(Debug, Default)]
 pub struct ServerStatus {
     pub cpu_load: f64,
     pub gpu_load: f64,
     pub vram_usage_ratio: f64,
+    pub workloads: Vec<MasterWorkload>,
+    // 현재 마스터 부하를 주로 만드는 도메인 (알 수 있을 때만)
+    pub load_domain: Option<String>,
 }
 
-#[derive(Debug)]
-pub struct StcScheduler {
-    pub master_id: String,
-    pub nodes: DashMap<String, NodeContext>,
-    pub thresholds: OverloadThresholds,
-    pub server_status: Mutex<ServerStatus>,
-    pub container_registry: RwLock<Vec<ContainerInfo>>,
+// 마스터에서 현재 돌고 있는 컨테이너 (샤딩 대상 선정용)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MasterWorkload {
+    pub container_id: String,
+    pub vram_gb: f64,
+    pub cpu_share: f64,
 }
 
-impl StcScheduler {
-    pub fn new(master_id: String, thresholds: OverloadThresholds) -> Self {
-        let registry = Self::load_containers_from_file("containers.json");
-        Self {
-            master_id,
-            nodes: DashMap::new(),
-            thresholds,
-            server_status: Mutex::new(ServerStatus::default()),
-            container_registry: RwLock::new(registry),
-        }
-    }
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverloadCause {
+    Cpu,
+    Vram,
+}
 
-    fn load_containers_from_file(path: &str) -> Vec<ContainerInfo> {
-        fs::read_to_string(path)
-            .ok()
-            .and_then(|c| serde_json::from_str(&c).ok())
-            .unwrap_or_default()
-    }
+// CPU/GPU 부하를 하나의 사용률로 합치는 방식
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LoadBlend {
+    Max,
+    Weighted { cpu_weight: f64 }, // gpu 가중치 = 1 - cpu_weight
+}
 
-    // ---------------- Node 등록 / 상태 ----------------
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RankingMode {
+    // effective OPI 내림차순
+    Performance,
+    // effective OPI가 하한 이상인 노드 중 가장 저렴한 노드 우선
+    CheapestAboveFloor { min_effective_opi: f64 },
+}
 
-    pub fn register_node_ctx(&self, mut ctx: NodeContext) {
-        // 초기 헬스/네트워크 값 세팅
-        ctx.net_rtt_ema_ms = 0.0;
-        ctx.health_state = HealthState::Healthy;
-        ctx.failure_count = 0;
-        ctx.is_quarantined = false;
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegressionPolicy {
+    Ignore,
+    Flag,       // 표시만 하고 스케줄링은 계속
+    Quarantine, // 조사 완료(clear_capability_regression)까지 격리
+}
 
-        let raw_score = self.calculate_raw_opi(&ctx);
-        ctx.current_tier = self.determine_tier(raw_score);
+// 노드가 자가 보고한 하드웨어 값의 상한 (넘으면 조작/버그로 간주)
+#[derive(Debug, Clone, Copy)]
+pub struct HardwareLimits {
+    pub max_pcie_lanes: u32,
+    pub max_pcie_gen: u32,
+    pub max_memory_bandwidth_gbps: f64,
+    pub max_compute_units: u32,
+    pub max_ram_mb: u64,
+}
 
-        let node_id = ctx.node_id.clone();
-self.nodes.insert(node_id.clone(), ctx);
+impl Default for HardwareLimits {
+    fn default() -> Self {
+        Self {
+            max_pcie_lanes: 64,
+            max_pcie_gen: 7,
+            max_memory_bandwidth_gbps: 10_000.0,
+            max_compute_units: 1024,
+            max_ram_mb: 4 * 1024 * 1024, // 4 TB
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HardwareValidation {
+    Clamp,  // 상한으로 잘라서 등록 (경고 로그)
+    Reject, // 등록 거부
+}
 
-if let Some(inserted) = self.nodes.get(&node_id) {
-    info!(
-        "[Scheduler] Node Registered: {} (OPI: {:.1}, Tier: {:?})",
-        node_id,
-        raw_score,
-        inserted.current_tier
-    );
-} else {
-    info!(
-        "[Scheduler] Node Registered: {} (OPI: {:.1})",
-        node_id, raw_score
-    );
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicateContainerPolicy {
+    RejectAll, // 중복이 하나라도 있으면 해당 로드/등록 전체 거부
+    KeepFirst, // 먼저 들어온 정의 유지
+    KeepLast,  // 나중 정의로 교체
+}
+
+// 등록 순서를 유지하는 목록 + id 인덱스. 둘은 항상 같은 락 아래에서 함께 바뀐다
+#[derive(Debug, Default)]
+pub struct ContainerRegistry {
+    entries: Vec<ContainerInfo>,
+    by_id: HashMap<String, usize>,
 }
 
+impl ContainerRegistry {
+    pub fn get(&self, id: &str) -> Option<&ContainerInfo> {
+        self.by_id.get(id).map(|&i| &self.entries[i])
     }
 
-    fn calculate_raw_opi(&self, node: &NodeContext) -> f64 {
-        let mut score = 0.0;
-        score += (node.total_ram_mb as f64 / 1024.0) * 5.0;
-        score += node.memory_bandwidth_gbps / 10.0;
-        score += (node.pcie_lanes * node.pcie_gen) as f64 * 2.0;
-        score += node.compute_units as f64 * 0.5;
-        if node.has_rocm {
-            score *= 1.1;
-        }
-        score
-    }
-
-    fn determine_tier(&self, score: f64) -> NodeTier {
-        if score >= 200.0 {
-            NodeTier::Tier1HighPerformance
-        } else if score >= 80.0 {
-            NodeTier::Tier2Standard
-        } else {
-            NodeTier::Tier3Mobile
-        }
+    pub fn iter(&self) -> std::slice::Iter<'_, ContainerInfo> {
+        self.entries.iter()
     }
 
-    pub fn update_node_status(
-        &self,
-        id: &str,
-        cpu: f64,
-        gpu: f64,
-        charging: bool,
-        net: String,
-        allowed: bool,
-    ) {
-        // 기존 API 유지용: RTT 없이 호출되면 RTT=0으로 처리
-        self.update_node_status_with_rtt(id, cpu, gpu, charging, net, allowed, 0.0);
+    pub fn len(&self) -> usize {
+        self.entries.len()
     }
 
-    pub fn update_node_status_with_rtt(
-        &self,
-        id: &str,
-        cpu: f64,
-        gpu: f64,
-        charging: bool,
-        net: String,
-        allowed: bool,
-        rtt_ms: f64,
-    ) {
-        if let Some(mut node) = self.nodes.get_mut(id) {
-            node.cpu_load = cpu;
-            node.gpu_load = gpu;
-            node.is_charging = charging;
-            node.network_type = net;
-            node.user_allowed = allowed;
-            node.last_seen = Instant::now();
-
-            // RTT EMA 업데이트 (0이면 업데이트 생략)
-            let gamma = 0.2_f64;
-            if rtt_ms > 0.0 {
-                if node.net_rtt_ema_ms <= 0.0 {
-                    node.net_rtt_ema_ms = rtt_ms;
-                } else {
-                    node.net_rtt_ema_ms =
-                        gamma * rtt_ms + (1.0 - gamma) * node.net_rtt_ema_ms;
-                }
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // 기존 항목과 새 항목 사이, 새 항목끼리의 id 중복을 정책에 따라 정리
+    fn merge(
+        &mut self,
+        incoming: Vec<ContainerInfo>,
+        policy: DuplicateContainerPolicy,
+    ) -> Result<(), ContainerRegistryError> {
+        let mut seen: HashSet<&str> = self.by_id.keys().map(String::as_str).collect();
+        let mut duplicates = Vec::new();
+        for c in &incoming {
+            if !seen.insert(&c.id) && !duplicates.contains(&c.id) {
+                duplicates.push(c.id.clone());
             }
+        }
 
-            // 헬스 상태 갱신
-            self.update_health_state(&mut node);
+        if !duplicates.is_empty() {
+            warn!(
+                "[Registry] Duplicate container id(s) {:?} (policy: {:?})",
+                duplicates, policy
+            );
+            if policy == DuplicateContainerPolicy::RejectAll {
+                return Err(ContainerRegistryError::DuplicateIds(duplicates));
+            }
+        }
 
-            // Tier 재계산 (하드웨어 기반)
-            let score = self.calculate_raw_opi(&node);
-            let new_tier = self.determine_tier(score);
-            if node.current_tier != new_tier {
-                info!(
-                    "[Tier Change] {}: {:?} -> {:?}",
-                    node.node_id, node.current_tier, new_tier
-                );
-                node.current_tier = new_tier;
+        for c in incoming {
+            match self.by_id.get(&c.id) {
+                Some(&i) if policy == DuplicateContainerPolicy::KeepLast => self.entries[i] = c,
+                Some(_) => {}
+                None => {
+                    self.by_id.insert(c.id.clone(), self.entries.len());
+                    self.entries.push(c);
+                }
             }
-        } else {
-            warn!("[Scheduler] update_node_status_with_rtt: unknown node_id={}", id);
         }
+        Ok(())
     }
+}
 
-    fn update_health_state(&self, node: &mut NodeContext) {
-        use HealthState::*;
+#[derive(Debug, Clone)]
+pub struct SchedulerConfig {
+    // false면 ACK 없이 발행 즉시 Active로 간주
+    pub require_shard_ack: bool,
+    // 0.0 = trust_score 미반영, 1.0 = effective OPI에 trust_score를 그대로 곱함
+    pub trust_weight: f64,
+    // 이 시간 이상 살아있으면 uptime 항목은 만점
+    pub trust_full_uptime_secs: f64,
+    // 노드별 부하 이력 최대 샘플 수
+    pub load_history_capacity: usize,
+    // 노드별 스케줄링 결정 이력 최대 개수 (0 = 기록 안 함)
+    pub decision_history_capacity: usize,
+    // 미등록 노드 Heartbeat 경고는 id당 이 간격에 한 번만 출력
+    pub unknown_node_log_interval_secs: f64,
+    // compute_units == 0 인 노드는 cpu_cores를 연산 유닛 점수로 사용
+    pub cpu_cores_as_compute_units: bool,
+    // Active Shard가 이 시간 동안 진행률 보고가 없으면 정체로 판단
+    pub progress_stall_secs: f64,
+    // None = 무제한
+    pub max_nodes: Option<usize>,
+    pub load_blend: LoadBlend,
+    // CpuBound/GpuBound 작업에서 주 자원 사용률의 가중치 (나머지는 보조 자원)
+    pub profile_primary_weight: f64,
+    pub ranking_mode: RankingMode,
+    // 기준값 대비 이 비율 이상 떨어지면 하드웨어 퇴행 (0.5 = 50% 하락)
+    pub capability_regression_ratio: f64,
+    pub capability_regression_policy: RegressionPolicy,
+    pub hardware_limits: HardwareLimits,
+    pub hardware_validation: HardwareValidation,
+    // 전 노드 격리 + 과부하 시 가장 덜 나쁜 노드 하나를 강제로 복귀시킬지
+    pub emergency_revive_last_resort: bool,
+    // 비상 복귀 노드는 이 시간 동안 Heartbeat 타임아웃으로 다시 격리하지 않음
+    pub emergency_revive_grace_secs: f64,
+    // storage_speed_mbps를 보고하지 않은 노드의 디스크 속도 추정치
+    pub default_storage_speed_mbps: f64,
+    // Shard를 자발적으로 반납한 노드는 이 시간 동안 effective OPI에 배수 적용
+    pub rejection_penalty_multiplier: f64,
+    pub rejection_penalty_secs: f64,
+    // 노드 수가 이 값을 넘으면 전체 스캔 대신 node_index에서 뽑은 표본만 평가 (fast path)
+    pub fast_path_fleet_threshold: usize,
+    pub fast_path_sample_size: usize,
+    pub fast_path_budget_ms: f64,
+    // Pending 상태로 이 시간 넘게 ACK가 없으면 reconcile에서 만료 처리
+    pub pending_ack_timeout_secs: f64,
+    // Tier 판정 점수 평활 계수 (1.0 = 최신 값만 사용, 작을수록 일시적 하락에 둔감)
+    pub tier_score_alpha: f64,
+    // Tier 경계(tier_boundaries) 주변 승급/강등에 필요한 OPI 여유폭 (0 = 히스테리시스 없음)
+    pub tier_hysteresis_margin: f64,
+    // (최소 OPI, Tier) 오름차순. 점수가 넘는 가장 높은 경계의 Tier, 첫 경계 미만이면 첫 Tier
+    pub tier_boundaries: Vec<(f64, NodeTier)>,
+    pub duplicate_container_policy: DuplicateContainerPolicy,
+    // 컨테이너 파일이 있는데 깨졌거나 RejectAll 정책에서 중복 id가 있을 때
+    // true = 빌더 실패, false = 경고 후 빈 레지스트리 (파일이 없으면 어느 쪽이든 빈 레지스트리)
+    pub strict_container_file: bool,
+    // 비어 있으면 도메인별 가속기 가중치 없음
+    pub accelerator_affinity: Vec<AcceleratorAffinity>,
+    // communicates_with 상대가 있는 zone의 노드에 곱하는 배수 (1.0 = 미사용)
+    pub peer_zone_multiplier: f64,
+    // false면 NaN은 0.0으로 보정해서 저장
+    pub reject_nan_master_status: bool,
+    // window 동안 Suspect 누적 시간이 한도를 넘으면 만성 불안정 노드로 표시 (true면 격리까지)
+    pub chronic_suspect_secs: f64,
+    pub chronic_suspect_window_secs: f64,
+    pub chronic_suspect_quarantine: bool,
+    // try_rehabilitate_node 후 Healthy로 돌아오기 위해 필요한 정상 상태 보고 수
+    pub probation_clean_updates: u32,
+    // 클라이언트의 다음 Offload가 이전 노드를 선호하는 기간 (0 = 사용 안 함)
+    pub client_affinity_ttl_secs: f64,
+    // 도메인별 과부하 임계값 (없는 도메인은 전역 임계값)
+    pub domain_thresholds: HashMap<String, OverloadThresholds>,
+    // 노드를 후보에서 빼는 부하 기준. Tier별 값이 없으면 node_overload 사용
+    // (예: Tier3Mobile cpu_max 0.6, Tier1HighPerformance cpu_max 0.95)
+    pub node_overload: OverloadThresholds,
+    pub tier_thresholds: HashMap<NodeTier, OverloadThresholds>,
+    // 누적 정상 가동 시간 보너스 (0.0 = 미사용, 0.2 = full_uptime에서 최대 +20%)
+    pub reliability_bonus_max: f64,
+    pub reliability_full_uptime_secs: f64,
+    // 충전 중이 아닌 Tier3Mobile 노드의 effective OPI 배수 / true면 아예 제외
+    pub battery_penalty_multiplier: f64,
+    pub exclude_mobile_on_battery: bool,
+    // true면 격리/Offline 노드도 "언젠가 가능"으로 간주해 실행 불가 판정에서 제외
+    pub feasibility_includes_unavailable: bool,
+    // RTT를 보고하기 시작했지만 표본이 rtt_min_samples 미만인 노드에 적용할 최소 net factor
+    // (1.0이면 페널티 없음. RTT를 보고하지 않는 노드나 복원된 EMA에는 적용하지 않는다)
+    pub rtt_min_samples: u32,
+    pub unconverged_net_factor: f64,
+    // SlaClass::Premium 컨테이너의 RTT 상한과 최소 복제본 수
+    pub premium_max_rtt_ms: f64,
+    pub premium_min_replicas: u32,
+    // 상태 저장 재시도 횟수/초기 backoff, 모두 실패하면 fallback 디렉터리에 저장 (None = 시스템 temp)
+    pub state_write_attempts: u32,
+    pub state_write_backoff_ms: u64,
+    pub state_fallback_dir: Option<PathBuf>,
+    // 과부하 1회에 샤드를 보낼 최대 노드 수 (후보 수보다 많으면 후보 수로 제한)
+    pub max_shard_fanout: usize,
+    // 후보 수와 후보 effective OPI 합이 둘 다 이 값 이상일 때만 샤딩 (약한 노드로의 역효과 방지)
+    pub min_viable_candidates: usize,
+    pub min_aggregate_opi: f64,
+    // RTT EMA 평활 계수 (새 샘플 비중)
+    pub rtt_ema_gamma: f64,
+    // 샘플이 현재 EMA의 이 배수를 넘으면 그 값으로 잘라서 반영 (GC pause 등 일회성 스파이크, 0 = 미사용).
+    // 기본 3.0: EMA 20ms 노드에 5000ms 샘플이 와도 EMA는 28ms 정도로만 오른다
+    pub rtt_spike_factor: f64,
+    // 예약 1건당 effective OPI 감쇠: 1 / (1 + penalty * 예약 수)
+    pub reservation_penalty: f64,
+    // 마지막 예약 후 이 시간이 지나면 Heartbeat 부하에 반영됐다고 보고 예약을 비운다 (0 = 미사용).
+    // 완료/거절 통지가 유실돼도 예약이 영구히 남지 않게 한다
+    pub reservation_ttl_secs: f64,
+    // Babel 스트림 노드의 RTT EMA 상한. 모든 후보가 넘으면 세션을 거절
+    pub babel_max_rtt_ms: f64,
+    // 재배치 제안 기준: 새 노드 effective OPI가 현재보다 이 비율 이상 높아야 함 (0.25 = 25%)
+    pub rebalance_min_improvement: f64,
+    pub network_penalties: NetworkPenalties,
+}
 
-        let now = Instant::now();
-        let since_seen = now.duration_since(node.last_seen).as_secs_f64();
+// update_node_status_with_rtt 인자와 동일 (배치 처리용)
+#[derive(Debug, Clone)]
+pub struct NodeStatusUpdate {
+    pub node_id: String,
+    pub cpu: f64,
+    pub gpu: f64,
+    pub charging: bool,
+    pub net: String,
+    pub allowed: bool,
+    pub rtt_ms: f64,
+}
 
-        // 하드 타임아웃 기준
-        if since_seen > 30.0 {
-            node.health_state = Quarantined;
-            node.is_quarantined = true;
-            return;
-        } else if since_seen > 10.0 {
-            node.health_state = Suspect;
-            // 스케줄링에서는 제외하되, 일단 완전 격리는 아님
-            return;
-        }
+#[derive(Debug, Clone, Serialize)]
+pub struct NodeStatusChange {
+    pub node_id: String,
+    pub tier_before: NodeTier,
+    pub tier_after: NodeTier,
+    pub health_before: HealthState,
+    pub health_after: HealthState,
+}
 
-        // RTT 기반 상태 (Heartbeat는 오고 있다고 가정)
-        if node.net_rtt_ema_ms > 150.0 {
-            node.health_state = Degraded;
-            node.is_quarantined = false;
-        } else {
-            node.health_state = Healthy;
-            node.is_quarantined = false;
-        }
+impl NodeStatusChange {
+    pub fn tier_changed(&self) -> bool {
+        self.tier_before != self.tier_after
     }
 
-    fn calculate_net_factor(&self, node: &NodeContext) -> f64 {
-        let base_rtt_ms = 10.0_f64; // "정상" LAN 기준
-        let max_penalty = 10.0_f64; // 최대 10배 페널티
+    pub fn health_changed(&self) -> bool {
+        self.health_before != self.health_after
+    }
+}
 
-        let rtt = if node.net_rtt_ema_ms <= 0.0 {
-            base_rtt_ms
-        } else {
-            node.net_rtt_ema_ms
-        };
+// 예: { domain: "Babel", accelerator: Npu, multiplier: 2.0 }
+#[derive(Debug, Clone)]
+pub struct AcceleratorAffinity {
+    pub domain: String,
+    pub accelerator: AcceleratorKind,
+    pub multiplier: f64,
+}
 
-        let raw = rtt / base_rtt_ms;
-        raw.clamp(1.0, max_penalty)
+impl Default for SchedulerConfig {
+    fn default() -> Self {
+        Self {
+            require_shard_ack: true,
+            trust_weight: 0.0,
+            trust_full_uptime_secs: 3600.0,
+            load_history_capacity: 120,
+            decision_history_capacity: 32,
+            unknown_node_log_interval_secs: 60.0,
+            cpu_cores_as_compute_units: true,
+            progress_stall_secs: 120.0,
+            max_nodes: None,
+            load_blend: LoadBlend::Max,
+            profile_primary_weight: 0.8,
+            ranking_mode: RankingMode::Performance,
+            capability_regression_ratio: 0.5,
+            capability_regression_policy: RegressionPolicy::Flag,
+            hardware_limits: HardwareLimits::default(),
+            hardware_validation: HardwareValidation::Clamp,
+            emergency_revive_last_resort: false,
+            emergency_revive_grace_secs: 60.0,
+            default_storage_speed_mbps: 200.0,
+            rejection_penalty_multiplier: 0.5,
+            rejection_penalty_secs: 60.0,
+            fast_path_fleet_threshold: 10_000,
+            fast_path_sample_size: 256,
+            fast_path_budget_ms: 5.0,
+            pending_ack_timeout_secs: 60.0,
+            tier_score_alpha: 0.3,
+            tier_hysteresis_margin: 5.0,
+            tier_boundaries: vec![
+                (0.0, NodeTier::Tier3Mobile),
+                (80.0, NodeTier::Tier2Standard),
+                (200.0, NodeTier::Tier1HighPerformance),
+            ],
+            duplicate_container_policy: DuplicateContainerPolicy::KeepFirst,
+            strict_container_file: false,
+            accelerator_affinity: Vec::new(),
+            peer_zone_multiplier: 1.25,
+            reject_nan_master_status: true,
+            chronic_suspect_secs: 120.0,
+            chronic_suspect_window_secs: 600.0,
+            chronic_suspect_quarantine: true,
+            probation_clean_updates: 3,
+            client_affinity_ttl_secs: 300.0,
+            domain_thresholds: HashMap::new(),
+            node_overload: OverloadThresholds {
+                cpu_max: 0.9,
+                gpu_max: 0.9,
+                vram_pressure_max: 1.0,
+            },
+            tier_thresholds: HashMap::new(),
+            reliability_bonus_max: 0.0,
+            reliability_full_uptime_secs: 3.0 * 86400.0,
+            battery_penalty_multiplier: 0.3,
+            exclude_mobile_on_battery: false,
+            feasibility_includes_unavailable: true,
+            rtt_min_samples: 3,
+            unconverged_net_factor: 3.0,
+            premium_max_rtt_ms: 50.0,
+            premium_min_replicas: 2,
+            state_write_attempts: 3,
+            state_write_backoff_ms: 50,
+            state_fallback_dir: None,
+            max_shard_fanout: 3,
+            min_viable_candidates: 1,
+            min_aggregate_opi: 0.0,
+            rtt_ema_gamma: 0.2,
+            rtt_spike_factor: 3.0,
+            reservation_penalty: 0.5,
+            reservation_ttl_secs: 30.0,
+            babel_max_rtt_ms: 120.0,
+            rebalance_min_improvement: 0.25,
+            network_penalties: NetworkPenalties::default(),
+        }
     }
+}
 
-    fn calculate_load_factor(&self, node: &NodeContext) -> f64 {
-        let load = node.gpu_load.max(node.cpu_load).clamp(0.0, 1.0);
-        1.0 - load
+impl SchedulerConfig {
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let boundaries = &self.tier_boundaries;
+        if boundaries.iter().any(|(min, _)| !min.is_finite())
+            || boundaries.windows(2).any(|w| w[0].0 >= w[1].0)
+        {
+            return Err(ConfigError::UnsortedTierBoundaries);
+        }
+        if boundaries.iter().any(|(_, t)| *t == NodeTier::Offline) {
+            return Err(ConfigError::OfflineTierBoundary);
+        }
+        if let Some(w) = boundaries.windows(2).find(|w| w[1].1 < w[0].1) {
+            return Err(ConfigError::TierOrderMismatch { min_score: w[1].0 });
+        }
+        for tier in [
+            NodeTier::Tier3Mobile,
+            NodeTier::Tier2Standard,
+            NodeTier::Tier1HighPerformance,
+        ] {
+            if !boundaries.iter().any(|(_, t)| *t == tier) {
+                return Err(ConfigError::MissingTier(tier));
+            }
+        }
+        Ok(())
     }
+}
 
-    fn calculate_effective_opi(&self, node: &NodeContext) -> f64 {
-        use HealthState::*;
-
-        if node.is_quarantined || matches!(node.health_state, Quarantined | Suspect) {
-            return 0.0;
-        }
+// 스케줄러가 관찰한 노드별 통계 (노드가 보고하는 값이 아님)
+#[derive(Debug, Clone)]
+pub struct NodeStats {
+    pub registered_at: Instant,
+    pub last_heartbeat_at: Option<Instant>,
+    pub heartbeat_interval_ema_secs: f64,
+    pub heartbeat_jitter_ema_secs: f64,
+    pub rtt_jitter_ema_ms: f64,
+    pub load_history: VecDeque<LoadSample>,
+    pub decision_history: VecDeque<HistoryEntry>,
+    // 등록 시점 하드웨어 기준값 (성능 퇴행 감지용)
+    pub baseline_bandwidth_gbps: f64,
+    pub baseline_compute_units: u32,
+    pub capability_regressed: bool,
+    // Tier 판정에 쓰는 raw OPI의 EMA (None = 아직 판정 전)
+    pub tier_score_ema: Option<f64>,
+    // 노드가 Heartbeat에 실어 보낸 마지막 타임스탬프 (노드 시계 기준)
+    pub last_reported_ts_ms: Option<u64>,
+    pub clock_regressions: u32,
+    // 현재 Suspect 구간 시작 시각과 지난 Suspect 구간들 (chronic_suspect_window_secs 이내)
+    pub suspect_since: Option<Instant>,
+    pub suspect_intervals: VecDeque<(Instant, Instant)>,
+    pub chronically_flaky: bool,
+    // healthy_uptime_secs 누적 기준 시각
+    pub uptime_marked_at: Option<Instant>,
+    // RTT > 0 으로 보고된 Heartbeat 수 (EMA 수렴 판단용)
+    pub rtt_samples: u32,
+    // 재활 중 Healthy 복귀까지 남은 정상 상태 보고 수 (0 = 재활 중 아님)
+    pub probation_remaining: u32,
+    // 관리자가 직접 격리한 노드 (unquarantine 전까지 Heartbeat로 해제되지 않음)
+    pub admin_quarantined: bool,
+    // 비상 복귀 유예 종료 시각 (이전에는 타임아웃 판정을 미룸)
+    pub revived_until: Option<Instant>,
+}
 
-        let hw = self.calculate_raw_opi(node);
-        let net = self.calculate_net_factor(node);
-        let load_factor = self.calculate_load_factor(node);
+#[derive(Debug, Clone, Copy)]
+pub struct UnknownHeartbeatLog {
+    pub last_logged_at: Instant,
+    pub suppressed: u64,
+}
 
-        (hw / net) * load_factor
-    }
+#[derive(Debug, Clone, Copy)]
+pub struct LoadSample {
+    pub at: Instant,
+    pub cpu_load: f64,
+    pub gpu_load: f64,
+}
 
-    pub fn update_master_status(&self, cpu: f64, gpu: f64, vram_ratio: f64) {
-        let mut status = self.server_status.lock();
-        status.cpu_load = cpu;
-        status.gpu_load = gpu;
-        status.vram_usage_ratio = vram_ratio;
-    }
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RejectReason {
+    NotAllowed,
+    CapabilityInvalid,
+    Offline,
+    Draining,
+    Overloaded,
+    Quarantined,
+    LowOpi,
+}
 
-    // ---------------- Smart Sharding ----------------
+#[derive(Debug, Clone, PartialEq)]
+pub enum HistoryEvent {
+    // 기본 후보 조건은 통과했지만 선택되지 않음 (컨테이너 요구사항 불일치 포함)
+    Considered { effective_opi: f64 },
+    ChosenForShard { container_id: String },
+    ChosenForOffload { container_id: String },
+    Rejected(RejectReason),
+}
 
-    pub fn check_server_overload_and_shard(&self) -> Vec<(String, ServerCommand)> {
-        let status = self.server_status.lock();
-        let mut commands = Vec::new();
+#[derive(Debug, Clone)]
+pub struct HistoryEntry {
+    pub at: Instant,
+    pub event: HistoryEvent,
+}
 
-        if status.cpu_load > self.thresholds.cpu_max
-            || status.vram_usage_ratio > self.thresholds.vram_pressure_max
-        {
-            let candidates = self.find_smart_candidates();
-            for node_id in candidates.iter().take(3) {
-                commands.push((node_id.clone(), self.create_shard_command()));
-            }
+impl NodeStats {
+    fn new(now: Instant, node: &NodeContext) -> Self {
+        Self {
+            registered_at: now,
+            last_heartbeat_at: None,
+            heartbeat_interval_ema_secs: 0.0,
+            heartbeat_jitter_ema_secs: 0.0,
+            rtt_jitter_ema_ms: 0.0,
+            load_history: VecDeque::new(),
+            decision_history: VecDeque::new(),
+            baseline_bandwidth_gbps: node.memory_bandwidth_gbps,
+            baseline_compute_units: node.compute_units,
+            capability_regressed: false,
+            tier_score_ema: None,
+            last_reported_ts_ms: None,
+            clock_regressions: 0,
+            suspect_since: None,
+            suspect_intervals: VecDeque::new(),
+            chronically_flaky: false,
+            uptime_marked_at: None,
+            rtt_samples: 0,
+            probation_remaining: 0,
+            admin_quarantined: false,
+            revived_until: None,
         }
-
-        commands
     }
+}
 
-    fn find_smart_candidates(&self) -> Vec<String> {
-        let mut candidates: Vec<(String, f64)> = self
-            .nodes
-            .iter()
-            .filter_map(|entry| {
-                let node = entry.value();
+// 서로 다른 Task의 shard 0끼리 충돌하지 않도록 (task_id, shard_index)로 구분
+pub type ShardKey = (String, u32);
 
-                if !node.user_allowed || node.current_tier == NodeTier::Offline {
-                    return None;
-                }
+// 한 번의 후보 평가에서 나온 노드별 결과 (결정 이력 기록용)
+pub type Evaluations = Vec<(String, HistoryEvent)>;
 
-                // 과부하 노드 제외
-                if node.cpu_load > 0.9 || node.gpu_load > 0.9 {
-                    return None;
-                }
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AssignmentStatus {
+    Pending,
+    Active,
+    Rejected,
+}
 
-                // 헬스/격리 상태 반영
-                if node.is_quarantined {
-                    return None;
-                }
+#[derive(Debug, Clone)]
+pub struct Assignment {
+    pub shard_id: String,
+    pub task_id: String,
+    pub shard_index: u32,
+    pub shard_total: u32,
+    pub container: String,
+    pub node_id: String,
+    pub status: AssignmentStatus,
+    pub created_at: Instant,
+    pub reject_reason: Option<String>,
+    pub progress: f64,                     // 0.0 ~ 1.0
+    pub last_progress_at: Option<Instant>, // Active 전환 시점부터 갱신
+}
 
-                let eff_opi = self.calculate_effective_opi(node);
-                if eff_opi <= 0.0 {
-                    return None;
-                }
+// rebalance_shards 제안. 실제 이동(취소 + 재발행)은 호출 측이 결정
+#[derive(Debug, Clone, Serialize)]
+pub struct Rebalance {
+    pub shard_id: String,
+    pub task_id: String,
+    pub shard_index: u32,
+    pub from_node: String,
+    pub to_node: String,
+    pub from_opi: f64,
+    pub to_opi: f64,
+}
 
-                Some((node.node_id.clone(), eff_opi))
-            })
-            .collect();
+#[derive(Debug, Clone, Serialize)]
+pub struct AssignmentRecord {
+    pub task_id: String,
+    pub shard_id: String,
+    pub shard_index: u32,
+    pub container: String,
+    pub node_id: String,
+    pub status: AssignmentStatus,
+    pub progress: f64,
+    pub age_secs: f64,
+}
 
-        candidates.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
-        candidates.into_iter().map(|(id, _)| id).collect()
-    }
+// Task 하나에 속한 Shard들의 집계 (shard_index 전체가 추적될 때 완료 판정 가능)
+#[derive(Debug, Clone, Serialize)]
+pub struct TaskStatus {
+    pub task_id: String,
+    pub shard_total: u32,
+    pub tracked_shards: u32,
+    pub pending: u32,
+    pub active: u32,
+    pub rejected: u32,
+    pub completed: u32,
+    // shard_total 기준 평균 진행률 (0.0 ~ 1.0)
+    pub progress: f64,
+    pub is_complete: bool,
+}
 
-    fn create_shard_command(&self) -> ServerCommand {
-        use stc::ShardPayload;
+#[derive(Debug, Clone, PartialEq)]
+pub enum SchedulerEvent {
+    // 재배치로 다시 발급된 경우 shard_count/node_ids는 이번에 새로 나간 Shard만 센다
+    TaskSharded {
+        task_id: String,
+        shard_count: u32,
+        node_ids: Vec<String>,
+    },
+    NodeRegistered {
+        node_id: String,
+        tier: NodeTier,
+    },
+    TierChanged {
+        node_id: String,
+        from: NodeTier,
+        to: NodeTier,
+    },
+    HealthChanged {
+        node_id: String,
+        from: HealthState,
+        to: HealthState,
+    },
+    NodeEvicted {
+        node_id: String,
+    },
+}
 
-        let shard_id = uuid::Uuid::new_v4().to_string();
+pub type EventCallback = Box<dyn Fn(SchedulerEvent) + Send + Sync>;
 
-        ServerCommand {
-            r#type: ServerCmdType::ShardTask as i32,
-            task_id: shard_id.clone(),
-            payload: Some(ServerPayload::Shard(ShardPayload {
-                shard_id,
-                shard_index: 0,
-                shard_total: 1,
-                data: Vec::new(),
-                next_container: "Programming".into(),
-                buffer_tag: "default".into(),
-            })),
-        }
+// 콜백은 별도 스레드에서 실행되므로 스케줄러 내부 잠금(DashMap 등)을 잡은 채로 호출되지 않는다.
+// 콜백 안에서 on_event를 다시 부르면 교착되므로 금지
+#[derive(Default)]
+pub struct EventSubscribers {
+    pub callbacks: Arc<RwLock<Vec<EventCallback>>>,
+    pub sender: Mutex<Option<mpsc::Sender<SchedulerEvent>>>,
+}
+
+impl std::fmt::Debug for EventSubscribers {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EventSubscribers")
+            .field("callbacks", &self.callbacks.read().len())
+            .finish()
     }
+}
 
-    // ---------------- Offload / Admin ----------------
+// 소비되지 않은 이벤트는 이 개수를 넘으면 오래된 것부터 버린다
+const MAX_PENDING_EVENTS: usize = 1024;
 
-    pub fn handle_offload_request(
-        &self,
-        client_id: &str,
+// 노드가 Shard 명령 수신 후 돌려보내는 응답
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AckPayload {
+    pub node_id: String,
+    pub task_id: String,
+    pub shard_index: u32,
+    pub shard_id: String,
+    pub accepted: bool,
+    #[serde(default)]
+    pub reason: String,
+}
+
+// Active 상태의 Shard 진행률 보고
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProgressPayload {
+    pub node_id: String,
+    pub task_id: String,
+    pub shard_index: u32,
+    pub shard_id: String,
+    pub progress: f64,
+}
+
+// 노드가 받은 Shard를 사유와 함께 자발적으로 반납 (게임 시작, 발열 한계 등)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShardRejectPayload {
+    pub node_id: String,
+    pub task_id: String,
+    pub shard_index: u32,
+    pub shard_id: String,
+    #[serde(default)]
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AssignmentError {
+    UnknownShard(String),
+    NodeMismatch {
+        shard_id: String,
+        expected: String,
+        got: String,
+    },
+    AlreadyResolved(String),
+    NotActive(String),
+}
+
+impl std::fmt::Display for AssignmentError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AssignmentError::UnknownShard(id) => write!(f, "unknown shard: {}", id),
+            AssignmentError::NodeMismatch {
+                shard_id,
+                expected,
+                got,
+            } => write!(
+                f,
+                "shard {} is assigned to {}, report came from {}",
+                shard_id, expected, got
+            ),
+            AssignmentError::AlreadyResolved(id) => write!(f, "shard {} is not pending", id),
+            AssignmentError::NotActive(id) => write!(f, "shard {} is not active", id),
+        }
+    }
+}
+
+impl std::error::Error for AssignmentError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SessionKind {
+    Offload,
+    Babel,
+    Assist,
+}
+
+// 클라이언트 요청으로 생성된 작업 (클라이언트 단위 정리/조회용)
+#[derive(Debug, Clone)]
+pub struct ClientSession {
+    pub task_id: String,
+    pub kind: SessionKind,
+    pub container_id: Option<String>,
+    pub node_id: Option<String>,
+    pub started_at: Instant,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RegistrationError {
+    CapacityExceeded {
+        max_nodes: usize,
+    },
+    // violations 예: "pcie_lanes=9999 (max 64)"
+    ImplausibleHardware {
+        node_id: String,
+        violations: Vec<String>,
+    },
+}
+
+impl std::fmt::Display for RegistrationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RegistrationError::CapacityExceeded { max_nodes } => {
+                write!(f, "cluster is at capacity ({} nodes)", max_nodes)
+            }
+            RegistrationError::ImplausibleHardware {
+                node_id,
+                violations,
+            } => write!(
+                f,
+                "{} reported implausible hardware: {}",
+                node_id,
+                violations.join(", ")
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RegistrationError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigError {
+    // 경계값이 오름차순이 아니거나 NaN/무한대
+    UnsortedTierBoundaries,
+    // 점수가 높을수록 Tier도 높아야 함
+    TierOrderMismatch { min_score: f64 },
+    MissingTier(NodeTier),
+    OfflineTierBoundary,
+    // reason에 serde_json이 찾은 줄/열이 포함된다
+    InvalidContainerFile { path: String, reason: String },
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::UnsortedTierBoundaries => {
+                write!(f, "tier boundaries must be finite and strictly ascending")
+            }
+            ConfigError::TierOrderMismatch { min_score } => write!(
+                f,
+                "tier boundary at {} is lower than the tier below it",
+                min_score
+            ),
+            ConfigError::MissingTier(tier) => {
+                write!(f, "tier boundaries do not cover {:?}", tier)
+            }
+            ConfigError::OfflineTierBoundary => {
+                write!(f, "Offline cannot be assigned by score")
+            }
+            ConfigError::InvalidContainerFile { path, reason } => {
+                write!(f, "container file {} is invalid: {}", path, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ContainerRegistryError {
+    DuplicateIds(Vec<String>),
+}
+
+impl std::fmt::Display for ContainerRegistryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ContainerRegistryError::DuplicateIds(ids) => {
+                write!(f, "duplicate container id(s): {}", ids.join(", "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for ContainerRegistryError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PlacementError {
+    UnknownContainer(String),
+    // fleet 어떤 노드도 요구 하드웨어를 갖추지 않음 (재시도 무의미)
+    Infeasible {
+        container_id: String,
+        reason: String,
+    },
+    // 가능한 노드는 있지만 지금은 여유가 없음 (재시도 가능)
+    NoCapacity(String),
+}
+
+impl PlacementError {
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, PlacementError::NoCapacity(_))
+    }
+}
+
+impl std::fmt::Display for PlacementError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PlacementError::UnknownContainer(id) => write!(f, "unknown container {}", id),
+            PlacementError::Infeasible {
+                container_id,
+                reason,
+            } => write!(
+                f,
+                "no node in fleet can ever host {}: {}",
+                container_id, reason
+            ),
+            PlacementError::NoCapacity(id) => {
+                write!(f, "no node currently has capacity for {}", id)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PlacementError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AdminError {
+    UnknownNode(String),
+    UnsupportedAction(String),
+    InvalidArgument(String),
+}
+
+impl std::fmt::Display for AdminError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AdminError::UnknownNode(id) => write!(f, "unknown node {}", id),
+            AdminError::UnsupportedAction(action) => write!(
+                f,
+                "unknown admin action '{}' (supported: {})",
+                action,
+                ADMIN_ACTIONS.join(", ")
+            ),
+            AdminError::InvalidArgument(msg) => write!(f, "invalid argument: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for AdminError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RehabilitationError {
+    UnknownNode(String),
+    NotQuarantined {
+        node_id: String,
+        state: HealthState,
+    },
+    NoFreshHeartbeat {
+        node_id: String,
+        since_seen_secs: f64,
+    },
+    HeldForRegression(String),
+}
+
+impl std::fmt::Display for RehabilitationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RehabilitationError::UnknownNode(id) => write!(f, "unknown node {}", id),
+            RehabilitationError::NotQuarantined { node_id, state } => {
+                write!(f, "{} is {:?}, not Quarantined", node_id, state)
+            }
+            RehabilitationError::NoFreshHeartbeat {
+                node_id,
+                since_seen_secs,
+            } => write!(
+                f,
+                "{} has no fresh heartbeat ({:.0}s ago)",
+                node_id, since_seen_secs
+            ),
+            RehabilitationError::HeldForRegression(id) => {
+                write!(f, "{} is held for capability regression", id)
+            }
+        }
+    }
+}
+
+impl std::error::Error for RehabilitationError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum SessionStatus {
+    Unassigned, // 아직 노드가 정해지지 않음
+    Assigned,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionView {
+    pub task_id: String,
+    pub container_id: Option<String>,
+    pub node_id: Option<String>,
+    pub status: SessionStatus,
+    pub age_secs: f64,
+}
+
+// 지원 도구용: "클라이언트 X는 지금 무엇을 하고 있나?"
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ClientSessionView {
+    pub client_id: String,
+    pub babel_sessions: Vec<SessionView>,
+    pub offloads: Vec<SessionView>,
+    pub assists: Vec<SessionView>,
+}
+
+// ---------------- 영속 상태 포맷 ----------------
+
+// 영속 포맷이 바뀌면 올리고 decode_state에 이전 버전의 마이그레이션 단계를 추가한다.
+// 이보다 새로운 버전은 조용히 깨지지 않도록 명시적으로 거부한다.
+//   v1: schema_version 필드가 없던 최초 포맷
+//   v2: zone/비용/모델/VRAM/드레인 등 노드 필드가 필수가 됨
+pub const STATE_SCHEMA_VERSION: u32 = 2;
+const DEFAULT_STATE_FILE: &str = "stc_state.json";
+const STATE_FALLBACK_FILE: &str = "stc_state.fallback.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PersistedState {
+    // 버전 필드가 생기기 전 파일에는 없다 (decode_state가 v1로 간주)
+    #[serde(default)]
+    pub schema_version: u32,
+    pub saved_at_unix_secs: u64,
+    pub master_id: String,
+    pub nodes: Vec<PersistedNode>,
+}
+
+// Instant는 직렬화할 수 없으므로 last_seen은 저장 시점 기준 경과 시간으로 남긴다
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PersistedNode {
+    pub node_id: String,
+    pub device_model: String,
+    pub cpu_cores: u32,
+    pub total_ram_mb: u64,
+    pub has_npu: bool,
+    pub has_cuda: bool,
+    pub has_rocm: bool,
+    pub has_intel_arc: bool,
+    pub pcie_lanes: u32,
+    pub pcie_gen: u32,
+    pub memory_bandwidth_gbps: f64,
+    pub compute_units: u32,
+    pub current_tier: NodeTier,
+    pub last_seen_age_secs: f64,
+    pub cpu_load: f64,
+    pub gpu_load: f64,
+    pub is_charging: bool,
+    pub network_type: String,
+    pub user_allowed: bool,
+    pub zone: Option<String>,
+    pub cost_per_hour: Option<f64>,
+    pub storage_speed_mbps: f64,
+    pub cached_models: Vec<String>,
+    pub advertised_models: HashSet<String>,
+    pub net_rtt_ema_ms: f64,
+    pub health_state: HealthState,
+    pub failure_count: u32,
+    pub is_quarantined: bool,
+    pub capability_invalid: bool,
+    pub total_vram_gb: u32,
+    pub healthy_uptime_secs: f64,
+    pub failure_domain: Option<String>,
+    pub is_draining: bool,
+    // 저장 시점의 effective OPI (warm start용)
+    pub last_effective_opi: f64,
+}
+
+impl PersistedNode {
+    fn from_node(node: &NodeContext, now: Instant, effective_opi: f64) -> Self {
+        Self {
+            node_id: node.node_id.clone(),
+            device_model: node.device_model.clone(),
+            cpu_cores: node.cpu_cores,
+            total_ram_mb: node.total_ram_mb,
+            has_npu: node.has_npu,
+            has_cuda: node.has_cuda,
+            has_rocm: node.has_rocm,
+            has_intel_arc: node.has_intel_arc,
+            pcie_lanes: node.pcie_lanes,
+            pcie_gen: node.pcie_gen,
+            memory_bandwidth_gbps: node.memory_bandwidth_gbps,
+            compute_units: node.compute_units,
+            current_tier: node.current_tier.clone(),
+            last_seen_age_secs: now.saturating_duration_since(node.last_seen).as_secs_f64(),
+            cpu_load: node.cpu_load,
+            gpu_load: node.gpu_load,
+            is_charging: node.is_charging,
+            network_type: node.network_type.clone(),
+            user_allowed: node.user_allowed,
+            zone: node.zone.clone(),
+            cost_per_hour: node.cost_per_hour,
+            storage_speed_mbps: node.storage_speed_mbps,
+            cached_models: node.cached_models.clone(),
+            advertised_models: node.advertised_models.clone(),
+            net_rtt_ema_ms: node.net_rtt_ema_ms,
+            health_state: node.health_state,
+            failure_count: node.failure_count,
+            is_quarantined: node.is_quarantined,
+            capability_invalid: node.capability_invalid,
+            total_vram_gb: node.total_vram_gb,
+            healthy_uptime_secs: node.healthy_uptime_secs,
+            failure_domain: node.failure_domain.clone(),
+            is_draining: node.is_draining,
+            last_effective_opi: effective_opi,
+        }
+    }
+
+    fn into_node(self, now: Instant) -> NodeContext {
+        let age = Duration::from_secs_f64(self.last_seen_age_secs.max(0.0));
+        let network_kind = NetworkKind::parse(&self.network_type);
+        NodeContext {
+            node_id: self.node_id,
+            device_model: self.device_model,
+            cpu_cores: self.cpu_cores,
+            total_ram_mb: self.total_ram_mb,
+            has_npu: self.has_npu,
+            has_cuda: self.has_cuda,
+            has_rocm: self.has_rocm,
+            has_intel_arc: self.has_intel_arc,
+            pcie_lanes: self.pcie_lanes,
+            pcie_gen: self.pcie_gen,
+            memory_bandwidth_gbps: self.memory_bandwidth_gbps,
+            compute_units: self.compute_units,
+            current_tier: self.current_tier,
+            last_seen: now.checked_sub(age).unwrap_or(now),
+            cpu_load: self.cpu_load,
+            gpu_load: self.gpu_load,
+            is_charging: self.is_charging,
+            network_type: self.network_type,
+            user_allowed: self.user_allowed,
+            zone: self.zone,
+            cost_per_hour: self.cost_per_hour,
+            storage_speed_mbps: self.storage_speed_mbps,
+            cached_models: self.cached_models,
+            advertised_models: self.advertised_models,
+            net_rtt_ema_ms: self.net_rtt_ema_ms,
+            health_state: self.health_state,
+            failure_count: self.failure_count,
+            is_quarantined: self.is_quarantined,
+            capability_invalid: self.capability_invalid,
+            total_vram_gb: self.total_vram_gb,
+            healthy_uptime_secs: self.healthy_uptime_secs,
+            failure_domain: self.failure_domain,
+            is_draining: self.is_draining,
+            pending_reservations: 0,
+            reserved_at: None,
+            network_kind,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum StateError {
+    Io(io::Error),
+    Parse(serde_json::Error),
+    UnsupportedVersion { found: u32, supported: u32 },
+    // 원래 경로 저장은 실패했지만 fallback 위치에는 저장됨
+    WrittenToFallback { fallback: PathBuf, cause: io::Error },
+}
+
+impl std::fmt::Display for StateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StateError::Io(e) => write!(f, "state io error: {}", e),
+            StateError::Parse(e) => write!(f, "state parse error: {}", e),
+            StateError::UnsupportedVersion { found, supported } => write!(
+                f,
+                "state schema v{} is newer than supported v{}",
+                found, supported
+            ),
+            StateError::WrittenToFallback { fallback, cause } => write!(
+                f,
+                "state write failed ({}), saved to fallback {}",
+                cause,
+                fallback.display()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for StateError {}
+
+impl From<io::Error> for StateError {
+    fn from(e: io::Error) -> Self {
+        StateError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for StateError {
+    fn from(e: serde_json::Error) -> Self {
+        StateError::Parse(e)
+    }
+}
+
+impl From<StateError> for io::Error {
+    fn from(e: StateError) -> Self {
+        match e {
+            StateError::Io(e) => e,
+            StateError::Parse(e) => io::Error::new(io::ErrorKind::InvalidData, e),
+            other => io::Error::other(other.to_string()),
+        }
+    }
+}
+
+// 임시 파일에 쓰고 rename 해서 중간에 죽어도 잘린 파일이 남지 않게 한다
+pub fn write_state_atomic(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+
+    fs::write(&tmp, bytes)?;
+    fs::rename(&tmp, path).inspect_err(|_| {
+        let _ = fs::remove_file(&tmp);
+    })
+}
+
+// 일시적 실패(디스크 풀, 권한 깜빡임)를 위해 backoff를 두 배씩 늘리며 재시도
+pub fn write_state_with_retry<W>(
+    mut write: W,
+    bytes: &[u8],
+    attempts: u32,
+    backoff_ms: u64,
+) -> io::Result<()>
+where
+    W: FnMut(&[u8]) -> io::Result<()>,
+{
+    let attempts = attempts.max(1);
+    let mut delay = backoff_ms;
+    let mut attempt = 1;
+    loop {
+        match write(bytes) {
+            Ok(()) => return Ok(()),
+            Err(e) if attempt >= attempts => return Err(e),
+            Err(e) => {
+                warn!(
+                    "[State] Write failed (attempt {}/{}): {}",
+                    attempt, attempts, e
+                );
+                std::thread::sleep(Duration::from_millis(delay));
+                delay = delay.saturating_mul(2);
+                attempt += 1;
+            }
+        }
+    }
+}
+
+// 직렬화와 저장 위치를 분리 (파일, S3, DB, KV 등)
+pub trait StateStore: Send + Sync + std::fmt::Debug {
+    fn save(&self, bytes: &[u8]) -> io::Result<()>;
+    // 저장된 상태가 없으면 None
+    fn load(&self) -> Option<Vec<u8>>;
+}
+
+#[derive(Debug, Clone)]
+pub struct FileStateStore {
+    pub path: PathBuf,
+}
+
+impl FileStateStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl StateStore for FileStateStore {
+    fn save(&self, bytes: &[u8]) -> io::Result<()> {
+        write_state_atomic(&self.path, bytes)
+    }
+
+    fn load(&self) -> Option<Vec<u8>> {
+        match fs::read(&self.path) {
+            Ok(bytes) => Some(bytes),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(e) => {
+                warn!("[State] Failed to read {}: {}", self.path.display(), e);
+                None
+            }
+        }
+    }
+}
+
+pub trait Clock: Send + Sync + std::fmt::Debug {
+    fn now(&self) -> Instant;
+}
+
+#[derive(Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+// 테스트용: 직접 advance 하기 전까지 시간이 멈춰 있음. clone은 같은 시각을 공유
+#[derive(Debug, Clone)]
+pub struct MockClock {
+    now: Arc<Mutex<Instant>>,
+}
+
+impl MockClock {
+    pub fn new() -> Self {
+        Self {
+            now: Arc::new(Mutex::new(Instant::now())),
+        }
+    }
+
+    pub fn advance(&self, by: Duration) {
+        *self.now.lock() += by;
+    }
+
+    pub fn advance_secs(&self, secs: f64) {
+        self.advance(Duration::from_secs_f64(secs.max(0.0)));
+    }
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        *self.now.lock()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PriorityBoost {
+    pub multiplier: f64,
+    pub expires_at: Option<Instant>, // None = 수동 해제 전까지 유지
+}
+
+impl PriorityBoost {
+    fn is_active(&self, now: Instant) -> bool {
+        self.expires_at.is_none_or(|t| now < t)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ReplicaPlacement {
+    pub container_id: String,
+    pub required: u32,
+    pub node_ids: Vec<String>,
+}
+
+impl ReplicaPlacement {
+    pub fn is_complete(&self) -> bool {
+        self.node_ids.len() >= self.required as usize
+    }
+}
+
+// 대시보드용 읽기 전용 노드 상태 (Instant 없이 직렬화 가능)
+#[derive(Debug, Clone, Serialize)]
+pub struct NodeSnapshot {
+    pub node_id: String,
+    pub current_tier: NodeTier,
+    pub health_state: HealthState,
+    pub net_rtt_ema_ms: f64,
+    pub effective_opi: f64,
+    pub cpu_load: f64,
+    pub gpu_load: f64,
+    pub is_quarantined: bool,
+    pub zone: Option<String>,
+    pub trust_score: f64,
+    pub limiting_factor: LimitingFactor,
+    pub healthy_uptime_secs: f64,
+    pub last_seen_age_secs: f64,
+    pub is_draining: bool,
+    pub pending_reservations: u32,
+}
+
+// effective = raw_hw / net_factor * load_factor * modifiers (헬스로 배제되면 0)
+#[derive(Debug, Clone, Serialize)]
+pub struct OpiBreakdown {
+    pub raw_hw: f64,
+    pub net_factor: f64,
+    pub load_factor: f64,
+    pub modifiers: f64,
+    pub effective: f64,
+    pub limiting_factor: LimitingFactor,
+}
+
+// effective OPI를 가장 크게 깎고 있는 항목
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LimitingFactor {
+    Network, // RTT 페널티
+    Load,    // CPU/GPU 부하
+    Health,  // 격리/Suspect/잘못된 성능 보고로 점수 0
+    None,
+}
+
+// 샤딩이 일어나지 않는 이유
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShardingBlock {
+    BelowThreshold,
+    NoCandidates,         // 과부하지만 받을 수 있는 노드가 없음
+    CapacityEmergency,    // 모든 노드 격리 상태
+    InsufficientCapacity, // 후보가 min_viable_candidates / min_aggregate_opi 미달
+}
+
+#[derive(Debug, Clone)]
+pub struct ShardingStatus {
+    pub cpu_load: f64,
+    pub gpu_load: f64,
+    pub vram_usage_ratio: f64,
+    pub thresholds: OverloadThresholds,
+    pub trigger: Option<OverloadCause>,
+    pub eligible_candidates: usize,
+    // None = 다음 check_server_overload_and_shard 호출에서 샤딩됨
+    pub blocked: Option<ShardingBlock>,
+}
+
+// tripped가 비어 있으면 과부하 아님
+#[derive(Debug, Clone, Default)]
+pub struct ShardOutcome {
+    pub tripped: Vec<OverloadCause>,
+    pub commands: Vec<(String, ServerCommand)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PlannedShard {
+    pub container_id: String,
+    pub node_id: String,
+    pub effective_opi: f64,
+}
+
+// plan_sharding 결과. cause가 None이면 과부하 아님
+#[derive(Debug, Clone, Default)]
+pub struct ShardingPlan {
+    pub tripped: Vec<OverloadCause>,
+    pub cause: Option<OverloadCause>,
+    pub candidate_count: usize,
+    pub aggregate_opi: f64,
+    // 후보 풀이 최소 용량 기준에 못 미쳐 샤딩을 보류함
+    pub suppressed: bool,
+    pub shards: Vec<PlannedShard>,
+    // 조건에 맞는 노드가 없어 배치하지 못한 컨테이너
+    pub unplaced: Vec<String>,
+    // 후보 선정 때 평가한 노드별 결과 (결정 이력은 재평가 없이 이 값으로 기록)
+    pub evaluated: Evaluations,
+}
+
+#[derive(Debug, Clone)]
+pub struct ClientAffinity {
+    pub node_id: String,
+    pub expires_at: Instant,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ReplaceReport {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub retained: Vec<String>,
+    // 제거된 노드에 남아있던 Assignment (재배치 대상)
+    pub orphaned: Vec<Assignment>,
+    // 등록 검증에 걸린 항목 (기존 노드였다면 이전 하드웨어 정보로 유지)
+    pub rejected: Vec<(String, RegistrationError)>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ZoneCapacity {
+    pub zone: String,
+    pub node_count: usize,
+    pub schedulable_nodes: usize,
+    pub total_effective_opi: f64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TickReport {
+    // (container_id, 새로 배치된 node_id)
+    pub replaced_replicas: Vec<(String, String)>,
+    pub expired_boosts: Vec<String>,
+    pub stalled_shards: Vec<String>,
+    pub reconciled: ReconcileReport,
+    // sweep_health에서 상태가 바뀐 노드 (node_id, 이전, 이후)
+    pub health_changes: Vec<(String, HealthState, HealthState)>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ReconcileReport {
+    // 노드가 사라진 Assignment의 shard_id
+    pub dangling_assignments: Vec<String>,
+    // ACK 없이 만료된 Pending Assignment의 shard_id
+    pub expired_pending: Vec<String>,
+    // (container_id, node_id) - 존재하지 않는 노드를 가리키던 replica 멤버
+    pub pruned_replica_members: Vec<(String, String)>,
+    // 노드 없이 남아있던 부가 상태 (stats, boost, warm start, 세션 배정) 수
+    pub orphaned_side_entries: usize,
+}
+
+impl ReconcileReport {
+    pub fn is_clean(&self) -> bool {
+        self.dangling_assignments.is_empty()
+            && self.expired_pending.is_empty()
+            && self.pruned_replica_members.is_empty()
+            && self.orphaned_side_entries == 0
+    }
+}
+
+#[derive(Debug)]
+pub struct StcScheduler {
+    pub master_id: String,
+    pub nodes: DashMap<String, NodeContext>,
+    // fast path 표본 추출용 node_id 목록. nodes와 같이 갱신하고 reconcile에서 보정
+    pub node_index: RwLock<Vec<String>>,
+    pub thresholds: OverloadThresholds,
+    pub health_thresholds: HealthThresholds,
+    pub opi_weights: OpiWeights,
+    pub server_status: Mutex<ServerStatus>,
+    pub container_registry: RwLock<ContainerRegistry>,
+    pub replica_sets: DashMap<String, Vec<String>>,
+    pub priority_boosts: DashMap<String, PriorityBoost>,
+    // node_id -> Shard 반납 페널티 해제 시각 (운영자 부스트와 별도로 곱해짐)
+    pub rejection_penalties: DashMap<String, Instant>,
+    pub clock: Box<dyn Clock>,
+    pub state_store: Box<dyn StateStore>,
+    pub config: SchedulerConfig,
+    // 전체 목록을 일관된 스냅샷으로 읽을 수 있도록 단일 락으로 보호
+    pub assignments: RwLock<HashMap<ShardKey, Assignment>>,
+    pub node_stats: DashMap<String, NodeStats>,
+    pub client_sessions: DashMap<String, Vec<ClientSession>>,
+    // client_id -> 최근 Offload 노드 (soft affinity)
+    pub client_affinity: DashMap<String, ClientAffinity>,
+    // 재시작 후 첫 Heartbeat 전까지 사용할 임시 effective OPI
+    pub warm_start_scores: DashMap<String, f64>,
+    pub unknown_heartbeats: DashMap<String, UnknownHeartbeatLog>,
+    pub capacity_emergency: AtomicBool,
+    // 노드 집합 변경(등록/해제/전체 교체)을 직렬화
+    pub membership: Mutex<()>,
+    pub events: Mutex<VecDeque<SchedulerEvent>>,
+    pub subscribers: EventSubscribers,
+    // Offload 후보 선택과 예약을 한 번에 처리 (동시 요청이 같은 노드를 고르지 않도록)
+    pub reservation: Mutex<()>,
+}
+
+// 설정 항목이 늘어도 new 시그니처를 바꾸지 않도록 생성은 빌더로 모은다
+pub struct StcSchedulerBuilder {
+    master_id: String,
+    thresholds: OverloadThresholds,
+    health_thresholds: HealthThresholds,
+    opi_weights: OpiWeights,
+    config: SchedulerConfig,
+    clock: Box<dyn Clock>,
+    state_store: Box<dyn StateStore>,
+    container_path: String,
+}
+
+impl StcSchedulerBuilder {
+    pub fn new(master_id: impl Into<String>, thresholds: OverloadThresholds) -> Self {
+        Self {
+            master_id: master_id.into(),
+            thresholds,
+            health_thresholds: HealthThresholds::default(),
+            opi_weights: OpiWeights::default(),
+            config: SchedulerConfig::default(),
+            clock: Box::new(SystemClock),
+            state_store: Box::new(FileStateStore::new(DEFAULT_STATE_FILE)),
+            container_path: DEFAULT_CONTAINER_FILE.to_string(),
+        }
+    }
+
+    pub fn with_health_thresholds(mut self, health_thresholds: HealthThresholds) -> Self {
+        self.health_thresholds = health_thresholds;
+        self
+    }
+
+    pub fn with_opi_weights(mut self, opi_weights: OpiWeights) -> Self {
+        self.opi_weights = opi_weights;
+        self
+    }
+
+    pub fn with_config(mut self, config: SchedulerConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    pub fn with_state_store(mut self, store: impl StateStore + 'static) -> Self {
+        self.state_store = Box::new(store);
+        self
+    }
+
+    pub fn with_container_path(mut self, path: impl Into<String>) -> Self {
+        self.container_path = path.into();
+        self
+    }
+
+    pub fn build(self) -> Result<StcScheduler, ConfigError> {
+        self.config.validate()?;
+
+        let containers = match StcScheduler::load_containers_from_file(&self.container_path) {
+            Ok(containers) => containers,
+            Err(e) if self.config.strict_container_file => return Err(e),
+            Err(e) => {
+                warn!("[Registry] {}; starting with no containers", e);
+                Vec::new()
+            }
+        };
+        let mut registry = ContainerRegistry::default();
+        if let Err(e) = registry.merge(containers, self.config.duplicate_container_policy) {
+            // RejectAll 중복도 파일이 깨진 것으로 보고 strict 설정을 따른다
+            if self.config.strict_container_file {
+                return Err(ConfigError::InvalidContainerFile {
+                    path: self.container_path,
+                    reason: e.to_string(),
+                });
+            }
+            error!("[Registry] {} rejected: {}", self.container_path, e);
+        }
+
+        Ok(StcScheduler {
+            master_id: self.master_id,
+            nodes: DashMap::new(),
+            node_index: RwLock::new(Vec::new()),
+            thresholds: self.thresholds,
+            health_thresholds: self.health_thresholds,
+            opi_weights: self.opi_weights,
+            server_status: Mutex::new(ServerStatus::default()),
+            container_registry: RwLock::new(registry),
+            replica_sets: DashMap::new(),
+            priority_boosts: DashMap::new(),
+            rejection_penalties: DashMap::new(),
+            clock: self.clock,
+            state_store: self.state_store,
+            config: self.config,
+            assignments: RwLock::new(HashMap::new()),
+            node_stats: DashMap::new(),
+            client_sessions: DashMap::new(),
+            client_affinity: DashMap::new(),
+            warm_start_scores: DashMap::new(),
+            unknown_heartbeats: DashMap::new(),
+            capacity_emergency: AtomicBool::new(false),
+            membership: Mutex::new(()),
+            events: Mutex::new(VecDeque::new()),
+            subscribers: EventSubscribers::default(),
+            reservation: Mutex::new(()),
+        })
+    }
+}
+
+impl StcScheduler {
+    pub fn new(
+        master_id: String,
+        thresholds: OverloadThresholds,
+        health_thresholds: HealthThresholds,
+    ) -> Self {
+        StcSchedulerBuilder::new(master_id, thresholds)
+            .with_health_thresholds(health_thresholds)
+            .build()
+            .expect("default scheduler config is valid")
+    }
+
+    pub fn builder(
+        master_id: impl Into<String>,
+        thresholds: OverloadThresholds,
+    ) -> StcSchedulerBuilder {
+        StcSchedulerBuilder::new(master_id, thresholds)
+    }
+
+    // 시작 시 한 번 호출해 지표 설명을 등록 (exporter는 앱에서 설치)
+    pub fn describe_metrics() {
+        metrics::describe();
+    }
+
+    // 기본 SystemClock 대신 다른 시계 사용 (테스트에서는 MockClock)
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    // 파일이 없으면 빈 레지스트리. 있는데 못 읽거나 깨졌으면 에러 (오타를 조용히 삼키지 않도록)
+    fn load_containers_from_file(path: &str) -> Result<Vec<ContainerInfo>, ConfigError> {
+        let invalid = |reason: String| ConfigError::InvalidContainerFile {
+            path: path.to_string(),
+            reason,
+        };
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                info!("[Registry] {} not found; no containers registered", path);
+                return Ok(Vec::new());
+            }
+            Err(e) => return Err(invalid(e.to_string())),
+        };
+        serde_json::from_str(&content).map_err(|e| invalid(e.to_string()))
+    }
+
+    // 재시작 없이 파일을 다시 읽어 레지스트리를 통째로 교체. 실패하면 기존 레지스트리 유지
+    pub fn reload_containers(&self, path: &str) -> Result<usize, String> {
+        let content =
+            fs::read_to_string(path).map_err(|e| format!("failed to read {}: {}", path, e))?;
+        let incoming: Vec<ContainerInfo> = serde_json::from_str(&content)
+            .map_err(|e| format!("failed to parse {}: {}", path, e))?;
+
+        let mut fresh = ContainerRegistry::default();
+        fresh
+            .merge(incoming, self.config.duplicate_container_policy)
+            .map_err(|e| e.to_string())?;
+
+        // 목록과 인덱스를 한 번에 교체하므로 읽는 쪽은 둘 중 하나만 바뀐 상태를 보지 않는다
+        let count = fresh.len();
+        *self.container_registry.write() = fresh;
+        info!("[Registry] Reloaded {} container(s) from {}", count, path);
+        Ok(count)
+    }
+
+    // 등록된 컨테이너 수를 반환. RejectAll 정책에서 중복이 있으면 레지스트리는 그대로 둔다
+    pub fn register_containers(
+        &self,
+        containers: Vec<ContainerInfo>,
+    ) -> Result<usize, ContainerRegistryError> {
+        let mut registry = self.container_registry.write();
+        registry.merge(containers, self.config.duplicate_container_policy)?;
+        Ok(registry.len())
+    }
+
+    // ---------------- Node 등록 / 상태 ----------------
+
+    pub fn register_node_ctx(&self, mut ctx: NodeContext) -> Result<(), RegistrationError> {
+        self.validate_hardware(&mut ctx)?;
+        let _membership = self.membership.lock();
+        self.ensure_capacity_for(&ctx.node_id)?;
+        self.install_node(ctx);
+        Ok(())
+    }
+
+    // 자가 보고 값이 상한을 넘으면 정책에 따라 잘라내거나 등록을 거부 (OPI 순위 독점 방지)
+    fn validate_hardware(&self, ctx: &mut NodeContext) -> Result<(), RegistrationError> {
+        let limits = self.config.hardware_limits;
+        let mut violations = Vec::new();
+
+        if ctx.pcie_lanes > limits.max_pcie_lanes {
+            violations.push(format!(
+                "pcie_lanes={} (max {})",
+                ctx.pcie_lanes, limits.max_pcie_lanes
+            ));
+            ctx.pcie_lanes = limits.max_pcie_lanes;
+        }
+        if ctx.pcie_gen > limits.max_pcie_gen {
+            violations.push(format!(
+                "pcie_gen={} (max {})",
+                ctx.pcie_gen, limits.max_pcie_gen
+            ));
+            ctx.pcie_gen = limits.max_pcie_gen;
+        }
+        // NaN은 여기서 걸리지 않고 OPI 계산에서 capability_invalid로 처리된다
+        if ctx.memory_bandwidth_gbps > limits.max_memory_bandwidth_gbps {
+            violations.push(format!(
+                "memory_bandwidth_gbps={} (max {})",
+                ctx.memory_bandwidth_gbps, limits.max_memory_bandwidth_gbps
+            ));
+            ctx.memory_bandwidth_gbps = limits.max_memory_bandwidth_gbps;
+        }
+        if ctx.compute_units > limits.max_compute_units {
+            violations.push(format!(
+                "compute_units={} (max {})",
+                ctx.compute_units, limits.max_compute_units
+            ));
+            ctx.compute_units = limits.max_compute_units;
+        }
+        if ctx.total_ram_mb > limits.max_ram_mb {
+            violations.push(format!(
+                "total_ram_mb={} (max {})",
+                ctx.total_ram_mb, limits.max_ram_mb
+            ));
+            ctx.total_ram_mb = limits.max_ram_mb;
+        }
+
+        if violations.is_empty() {
+            return Ok(());
+        }
+        match self.config.hardware_validation {
+            HardwareValidation::Clamp => {
+                warn!(
+                    "[Scheduler] Clamped implausible hardware on {}: {}",
+                    ctx.node_id,
+                    violations.join(", ")
+                );
+                Ok(())
+            }
+            HardwareValidation::Reject => {
+                warn!(
+                    "[Scheduler] Rejected {}: implausible hardware {}",
+                    ctx.node_id,
+                    violations.join(", ")
+                );
+                Err(RegistrationError::ImplausibleHardware {
+                    node_id: ctx.node_id.clone(),
+                    violations,
+                })
+            }
+        }
+    }
+
+    fn install_node(&self, mut ctx: NodeContext) {
+        // 초기 헬스/네트워크 값 세팅
+        ctx.net_rtt_ema_ms = 0.0;
+        ctx.health_state = HealthState::Healthy;
+        ctx.failure_count = 0;
+        ctx.is_quarantined = false;
+        ctx.capability_invalid = false;
+        ctx.healthy_uptime_secs = 0.0;
+        ctx.pending_reservations = 0;
+        ctx.reserved_at = None;
+        ctx.network_kind = NetworkKind::parse(&ctx.network_type);
+        ctx.last_seen = self.clock.now();
+
+        let raw_score = self.calculate_raw_opi(&ctx);
+        match self.determine_tier(raw_score) {
+            Some(tier) => ctx.current_tier = tier,
+            None => self.mark_capability_invalid(&mut ctx, raw_score),
+        }
+
+        let node_id = ctx.node_id.clone();
+        let tier = ctx.current_tier.clone();
+        let mut stats = NodeStats::new(self.clock.now(), &ctx);
+        stats.tier_score_ema = raw_score.is_finite().then_some(raw_score);
+        self.node_stats.insert(node_id.clone(), stats);
+        metrics::node_added(&ctx.current_tier, ctx.health_state);
+        match self.nodes.insert(node_id.clone(), ctx) {
+            Some(old) => metrics::node_removed(&old.current_tier, old.health_state),
+            None => self.node_index.write().push(node_id.clone()),
+        }
+        self.emit_event(SchedulerEvent::NodeRegistered {
+            node_id: node_id.clone(),
+            tier: tier.clone(),
+        });
+
+        info!(
+            "[Scheduler] Node Registered: {} (OPI: {:.1}, Tier: {:?})",
+            node_id, raw_score, tier
+        );
+    }
+
+    // 외부(클러스터 매니저 등)가 노드 목록의 원본일 때 전체 집합을 통째로 교체.
+    // 겹치는 id는 하드웨어 정보만 갱신하고 RTT EMA/헬스/통계는 유지한다
+    pub fn replace_nodes(&self, nodes: Vec<NodeContext>) -> ReplaceReport {
+        let _membership = self.membership.lock();
+        let mut report = ReplaceReport::default();
+
+        let incoming: HashSet<String> = nodes.iter().map(|n| n.node_id.clone()).collect();
+        let stale: Vec<String> = self
+            .nodes
+            .iter()
+            .map(|e| e.key().clone())
+            .filter(|id| !incoming.contains(id))
+            .collect();
+        for id in stale {
+            self.remove_node_state(&id);
+            report.orphaned.extend(self.take_node_assignments(&id));
+            report.removed.push(id);
+        }
+
+        for mut ctx in nodes {
+            if let Err(e) = self.validate_hardware(&mut ctx) {
+                report.rejected.push((ctx.node_id.clone(), e));
+                continue;
+            }
+            let Some(mut node) = self.nodes.get_mut(&ctx.node_id) else {
+                // 새 노드는 단건 등록과 같은 max_nodes 상한을 따른다
+                if let Err(e) = self.ensure_capacity_for(&ctx.node_id) {
+                    report.rejected.push((ctx.node_id.clone(), e));
+                    continue;
+                }
+                report.added.push(ctx.node_id.clone());
+                self.install_node(ctx);
+                continue;
+            };
+            let NodeContext {
+                net_rtt_ema_ms,
+                health_state,
+                failure_count,
+                is_quarantined,
+                last_seen,
+                healthy_uptime_secs,
+                is_draining,
+                pending_reservations,
+                reserved_at,
+                ..
+            } = *node;
+            *node = NodeContext {
+                net_rtt_ema_ms,
+                health_state,
+                failure_count,
+                is_quarantined,
+                last_seen,
+                healthy_uptime_secs,
+                is_draining,
+                pending_reservations,
+                reserved_at,
+                ..ctx
+            };
+            node.network_kind = NetworkKind::parse(&node.network_type);
+            self.refresh_tier(&mut node);
+            report.retained.push(node.node_id.clone());
+        }
+
+        info!(
+            "[Scheduler] Node set replaced: +{} -{} ={} ({} orphaned assignment(s), {} rejected)",
+            report.added.len(),
+            report.removed.len(),
+            report.retained.len(),
+            report.orphaned.len(),
+            report.rejected.len()
+        );
+        report
+    }
+
+    // 클러스터 상한 도달 시 응답이 끊긴(타임아웃) 격리 노드 중 가장 오래 죽어있던 노드를 밀어내고,
+    // 없으면 거절. 실패 누적으로 막 격리됐지만 Heartbeat는 오는 노드는 밀어내지 않는다
+    // (검사와 삽입이 원자적이지 않으므로 동시 등록 시 상한을 약간 넘을 수 있음)
+    fn ensure_capacity_for(&self, node_id: &str) -> Result<(), RegistrationError> {
+        let Some(max_nodes) = self.config.max_nodes else {
+            return Ok(());
+        };
+        if self.nodes.contains_key(node_id) || self.nodes.len() < max_nodes {
+            return Ok(());
+        }
+
+        let victim = self
+            .nodes
+            .iter()
+            .filter(|e| e.value().is_quarantined && self.is_timed_out(e.value()))
+            .min_by_key(|e| e.value().last_seen)
+            .map(|e| e.key().clone());
+
+        match victim {
+            Some(victim) => {
+                self.remove_node_state(&victim);
+                let orphaned = self.take_node_assignments(&victim);
+                info!(
+                    "[Scheduler] Cluster full ({}), evicted dead node {} for {} ({} orphaned assignment(s))",
+                    max_nodes,
+                    victim,
+                    node_id,
+                    orphaned.len()
+                );
+                if !orphaned.is_empty() {
+                    warn!(
+                        "[Scheduler] Shards left on evicted node {}: {:?}",
+                        victim,
+                        orphaned.iter().map(|a| &a.shard_id).collect::<Vec<_>>()
+                    );
+                }
+                Ok(())
+            }
+            None => {
+                warn!(
+                    "[Scheduler] Cluster full ({}), refusing registration of {}",
+                    max_nodes, node_id
+                );
+                Err(RegistrationError::CapacityExceeded { max_nodes })
+            }
+        }
+    }
+
+    // 노드를 제거하고, 그 노드에 남아있던 (Pending/Active) 할당을 돌려준다
+    pub fn deregister_node(&self, id: &str) -> Vec<Assignment> {
+        let _membership = self.membership.lock();
+        if self.remove_node_state(id).is_none() {
+            warn!("[Scheduler] deregister_node: unknown node_id={}", id);
+            return Vec::new();
+        }
+
+        let orphaned = self.take_node_assignments(id);
+        info!(
+            "[Scheduler] Node Deregistered: {} ({} orphaned assignment(s))",
+            id,
+            orphaned.len()
+        );
+        orphaned
+    }
+
+    // 노드에 걸린 할당을 모두 지우고, 그중 아직 유효했던 (Pending/Active) 것을 돌려준다
+    fn take_node_assignments(&self, id: &str) -> Vec<Assignment> {
+        let mut orphaned = Vec::new();
+        self.assignments.write().retain(|_, a| {
+            if a.node_id != id {
+                return true;
+            }
+            if a.status != AssignmentStatus::Rejected {
+                orphaned.push(a.clone());
+            }
+            false
+        });
+        orphaned
+    }
+
+    // 오래 소식이 없는 노드를 맵에서 제거 (격리 노드가 무한히 쌓이는 것 방지).
+    // 후보를 모은 뒤 remove_if로 다시 확인하므로, 그 사이 Heartbeat가 온 노드는 살아남는다
+    pub fn evict_stale_nodes(&self, max_idle_secs: f64) -> Vec<String> {
+        let _membership = self.membership.lock();
+        let now = self.clock.now();
+        let is_stale = |n: &NodeContext| {
+            now.saturating_duration_since(n.last_seen).as_secs_f64() > max_idle_secs
+        };
+
+        let stale: Vec<String> = self
+            .nodes
+            .iter()
+            .filter(|e| is_stale(e.value()))
+            .map(|e| e.key().clone())
+            .collect();
+
+        let mut evicted = Vec::new();
+        for id in stale {
+            let Some((_, node)) = self.nodes.remove_if(&id, |_, n| is_stale(n)) else {
+                continue;
+            };
+            self.note_node_removed(&node);
+            self.remove_node_state(&id);
+            evicted.push(id);
+        }
+
+        if !evicted.is_empty() {
+            // 남은 Assignment/replica 멤버는 다음 reconcile에서 정리됨
+            info!(
+                "[Scheduler] Evicted {} stale node(s): {:?}",
+                evicted.len(),
+                evicted
+            );
+        }
+        evicted
+    }
+
+    // 노드와 노드에 딸린 스케줄러 측 상태를 함께 제거
+    fn remove_node_state(&self, id: &str) -> Option<NodeContext> {
+        self.node_stats.remove(id);
+        self.priority_boosts.remove(id);
+        self.rejection_penalties.remove(id);
+        self.warm_start_scores.remove(id);
+        let (_, node) = self.nodes.remove(id)?;
+        self.note_node_removed(&node);
+        Some(node)
+    }
+
+    fn calculate_raw_opi(&self, node: &NodeContext) -> f64 {
+        let w = &self.opi_weights;
+        let ram = (node.total_ram_mb as f64 / 1024.0) * w.ram_per_gb;
+        let bandwidth = node.memory_bandwidth_gbps / w.bandwidth_divisor;
+        let pcie = (node.pcie_lanes * node.pcie_gen) as f64 * w.pcie_per_lane_gen;
+        let compute = self.effective_compute_units(node) as f64 * w.compute_per_unit;
+
+        let score = Self::cap_term(ram, bandwidth + pcie + compute, w.ram_cap)
+            + Self::cap_term(bandwidth, ram + pcie + compute, w.bandwidth_cap)
+            + Self::cap_term(pcie, ram + bandwidth + compute, w.pcie_cap)
+            + Self::cap_term(compute, ram + bandwidth + pcie, w.compute_cap);
+        score * Self::accelerator_multiplier(node, w)
+    }
+
+    fn accelerator_multiplier(node: &NodeContext, w: &OpiWeights) -> f64 {
+        [
+            (node.has_rocm, w.rocm_multiplier),
+            (node.has_cuda, w.cuda_multiplier),
+            (node.has_intel_arc, w.intel_arc_multiplier),
+            (node.has_npu, w.npu_multiplier),
+        ]
+        .into_iter()
+        .filter(|(present, _)| *present)
+        .map(|(_, m)| m)
+        .fold(1.0, f64::max)
+    }
+
+    // term / (term + others) <= cap 이 되도록 term을 자른다
+    // (다른 항목이 모두 0이면 비율이 정의되지 않으므로 그대로 둔다)
+    fn cap_term(term: f64, others: f64, cap: Option<f64>) -> f64 {
+        match cap {
+            Some(f) if f < 1.0 && others > 0.0 => term.min(others * f.max(0.0) / (1.0 - f)),
+            _ => term,
+        }
+    }
+
+    // CPU 전용 노드는 CPU 코어 수로 연산 유닛 항목을 대신해 0점 처리되지 않게 한다
+    fn effective_compute_units(&self, node: &NodeContext) -> u32 {
+        if node.is_cpu_only() && self.config.cpu_cores_as_compute_units {
+            node.cpu_cores
+        } else {
+            node.compute_units
+        }
+    }
+
+    // NaN/inf 점수는 어떤 Tier에도 속하지 않음 (조용히 Tier3로 떨어지지 않도록)
+    fn determine_tier(&self, score: f64) -> Option<NodeTier> {
+        if !score.is_finite() {
+            return None;
+        }
+
+        let boundaries = &self.config.tier_boundaries;
+        boundaries
+            .iter()
+            .rev()
+            .find(|(min, _)| score >= *min)
+            .or_else(|| boundaries.first())
+            .map(|(_, tier)| tier.clone())
+    }
+
+    // 경계를 margin 이상 넘어야 승급, margin 이상 밑돌아야 강등 (경계 근처 진동 방지)
+    fn determine_tier_with_hysteresis(&self, score: f64, current: &NodeTier) -> Option<NodeTier> {
+        let margin = self.config.tier_hysteresis_margin.max(0.0);
+        let up = self.determine_tier(score - margin)?;
+        let down = self.determine_tier(score + margin)?;
+
+        Some(if up > *current {
+            up
+        } else if down < *current {
+            down
+        } else {
+            current.clone()
+        })
+    }
+
+    fn mark_capability_invalid(&self, node: &mut NodeContext, score: f64) {
+        error!(
+            "[Scheduler] Invalid capability on {}: OPI={} (ram={}MB, bw={}GB/s, pcie={}x{}, cu={})",
+            node.node_id,
+            score,
+            node.total_ram_mb,
+            node.memory_bandwidth_gbps,
+            node.pcie_lanes,
+            node.pcie_gen,
+            node.compute_units
+        );
+        node.capability_invalid = true;
+        node.current_tier = NodeTier::Offline;
+    }
+
+    fn smooth_tier_score(&self, node: &NodeContext, raw: f64) -> f64 {
+        let alpha = self.config.tier_score_alpha.clamp(0.0, 1.0);
+        let mut stats = self
+            .node_stats
+            .entry(node.node_id.clone())
+            .or_insert_with(|| NodeStats::new(self.clock.now(), node));
+
+        let smoothed = match stats.tier_score_ema {
+            Some(prev) => alpha * raw + (1.0 - alpha) * prev,
+            None => raw,
+        };
+        stats.tier_score_ema = Some(smoothed);
+        smoothed
+    }
+
+    pub fn smoothed_tier_score(&self, id: &str) -> Option<f64> {
+        self.node_stats.get(id).and_then(|s| s.tier_score_ema)
+    }
+
+    pub fn update_node_status(
+        &self,
+        id: &str,
+        cpu: f64,
+        gpu: f64,
+        charging: bool,
+        net: String,
+        allowed: bool,
+    ) {
+        // 기존 API 유지용: RTT 없이 호출되면 RTT=0으로 처리
+        self.update_node_status_with_rtt(id, cpu, gpu, charging, net, allowed, 0.0);
+    }
+
+    pub fn update_node_status_with_rtt(
+        &self,
+        id: &str,
+        cpu: f64,
+        gpu: f64,
+        charging: bool,
+        net: String,
+        allowed: bool,
+        rtt_ms: f64,
+    ) {
+        self.apply_status_update(
+            "update_node_status_with_rtt",
+            NodeStatusUpdate {
+                node_id: id.to_string(),
+                cpu,
+                gpu,
+                charging,
+                net,
+                allowed,
+                rtt_ms,
+            },
+        );
+    }
+
+    // 여러 노드의 Heartbeat를 한 번에 반영하고 Tier/헬스가 바뀐 노드만 돌려준다
+    pub fn update_node_statuses(&self, batch: Vec<NodeStatusUpdate>) -> Vec<NodeStatusChange> {
+        let total = batch.len();
+        let changes: Vec<NodeStatusChange> = batch
+            .into_iter()
+            .filter_map(|update| self.apply_status_update("update_node_statuses", update))
+            .filter(|c| c.tier_changed() || c.health_changed())
+            .collect();
+
+        if !changes.is_empty() {
+            info!(
+                "[Heartbeat] Batch of {}: {} node(s) changed tier/health",
+                total,
+                changes.len()
+            );
+        }
+        changes
+    }
+
+    // 미등록 노드면 None. source는 로그에 남길 공개 API 이름
+    fn apply_status_update(
+        &self,
+        source: &str,
+        update: NodeStatusUpdate,
+    ) -> Option<NodeStatusChange> {
+        let id = update.node_id.as_str();
+        let Some(mut node) = self.nodes.get_mut(id) else {
+            self.log_unknown_heartbeat(source, id);
+            return None;
+        };
+        let tier_before = node.current_tier.clone();
+        let health_before = node.health_state;
+
+        node.cpu_load = update.cpu;
+        node.gpu_load = update.gpu;
+        node.is_charging = update.charging;
+        node.network_kind = NetworkKind::parse(&update.net);
+        node.network_type = update.net;
+        node.user_allowed = update.allowed;
+        node.last_seen = self.clock.now();
+        Self::expire_reservations(&mut node, self.config.reservation_ttl_secs);
+        // 연속 실패로 격리된 노드는 Heartbeat만으로 복귀하지 않음 (try_rehabilitate_node 필요)
+        if node.failure_count < self.health_thresholds.max_consecutive_failures {
+            node.failure_count = 0;
+        }
+
+        // 실제 Heartbeat가 왔으므로 warm-start 임시 점수는 폐기
+        self.warm_start_scores.remove(id);
+
+        // RTT EMA 업데이트 (0이면 업데이트 생략)
+        let rtt_ms = update.rtt_ms;
+        let gamma = self.config.rtt_ema_gamma.clamp(0.0, 1.0);
+        let prev_rtt_ema = node.net_rtt_ema_ms;
+        if rtt_ms > 0.0 {
+            if node.net_rtt_ema_ms <= 0.0 {
+                node.net_rtt_ema_ms = rtt_ms;
+            } else {
+                let sample = self.clamp_rtt_spike(id, rtt_ms, prev_rtt_ema);
+                node.net_rtt_ema_ms = gamma * sample + (1.0 - gamma) * node.net_rtt_ema_ms;
+            }
+        }
+
+        self.record_heartbeat_stats(&node, rtt_ms, prev_rtt_ema, gamma);
+
+        // 헬스 상태 갱신
+        self.update_health_state(&mut node);
+
+        // Tier 재계산 (하드웨어 기반)
+        self.refresh_tier(&mut node);
+
+        Some(NodeStatusChange {
+            node_id: update.node_id.clone(),
+            tier_before,
+            tier_after: node.current_tier.clone(),
+            health_before,
+            health_after: node.health_state,
+        })
+    }
+
+    // 마지막 예약 후 TTL이 지났으면 그 작업은 이번 Heartbeat 부하에 이미 잡혔다고 본다
+    fn expire_reservations(node: &mut NodeContext, ttl_secs: f64) {
+        let Some(reserved_at) = node.reserved_at else {
+            return;
+        };
+        if ttl_secs <= 0.0
+            || node
+                .last_seen
+                .saturating_duration_since(reserved_at)
+                .as_secs_f64()
+                < ttl_secs
+        {
+            return;
+        }
+        if node.pending_reservations > 0 {
+            debug!(
+                "[Offload] Node {} reservations expired after {:.0}s: {} cleared",
+                node.node_id, ttl_secs, node.pending_reservations
+            );
+        }
+        node.pending_reservations = 0;
+        node.reserved_at = None;
+    }
+
+    fn clamp_rtt_spike(&self, id: &str, rtt_ms: f64, ema_ms: f64) -> f64 {
+        let factor = self.config.rtt_spike_factor;
+        if factor <= 0.0 || rtt_ms <= ema_ms * factor {
+            return rtt_ms;
+        }
+        let clamped = ema_ms * factor;
+        debug!(
+            "[RTT] {} spike {:.0}ms clamped to {:.0}ms (ema {:.0}ms)",
+            id, rtt_ms, clamped, ema_ms
+        );
+        clamped
+    }
+
+    // Shard 명령 타임아웃/중도 사망 등 실제 작업 실패를 보고
+    pub fn record_node_failure(&self, id: &str) -> Option<u32> {
+        let mut node = self.nodes.get_mut(id)?;
+        node.failure_count = node.failure_count.saturating_add(1);
+        let count = node.failure_count;
+
+        if count >= self.health_thresholds.max_consecutive_failures && !node.is_quarantined {
+            warn!(
+                "[Health] {} quarantined after {} consecutive failure(s)",
+                id, count
+            );
+        }
+        self.update_health_state(&mut node);
+        Some(count)
+    }
+
+    // 부하/RTT 수집 없이 생존만 알리는 경량 Heartbeat (지표 수집 공백 동안 Suspect 방지)
+    pub fn touch_node(&self, id: &str) -> bool {
+        let Some(mut node) = self.nodes.get_mut(id) else {
+            self.log_unknown_heartbeat("touch_node", id);
+            return false;
+        };
+        node.last_seen = self.clock.now();
+        self.update_health_state(&mut node);
+        true
+    }
+
+    fn refresh_tier(&self, node: &mut NodeContext) {
+        let before = node.current_tier.clone();
+        self.apply_tier(node);
+        self.note_tier_change(&node.node_id, &before, &node.current_tier);
+    }
+
+    fn apply_tier(&self, node: &mut NodeContext) {
+        // 응답 없는 노드의 Offline 표시는 Heartbeat가 다시 올 때까지 유지
+        if self.is_timed_out(node) {
+            return;
+        }
+        let raw = self.calculate_raw_opi(node);
+        // 비정상 점수는 평활하지 않고 바로 판정 (EMA 오염 방지)
+        let score = if raw.is_finite() {
+            self.smooth_tier_score(node, raw)
+        } else {
+            raw
+        };
+        match self.determine_tier_with_hysteresis(score, &node.current_tier) {
+            Some(new_tier) => {
+                node.capability_invalid = false;
+                if node.current_tier != new_tier {
+                    info!(
+                        "[Tier Change] {}: {:?} -> {:?}",
+                        node.node_id, node.current_tier, new_tier
+                    );
+                    node.current_tier = new_tier;
+                }
+            }
+            None if !node.capability_invalid => self.mark_capability_invalid(node, score),
+            None => {}
+        }
+    }
+
+    // ---------------- 성능 퇴행 감지 ----------------
+
+    // 에이전트가 하드웨어 값을 다시 보고할 때 호출. 기준값 대비 급락하면 퇴행으로 판단.
+    // 등록 때와 같은 상한 검사를 거치며, Reject 정책에서 걸리면 이전 값을 유지한다
+    pub fn update_node_capabilities(
+        &self,
+        id: &str,
+        memory_bandwidth_gbps: f64,
+        compute_units: u32,
+    ) -> Option<bool> {
+        let mut node = self.nodes.get_mut(id)?;
+        let previous = (node.memory_bandwidth_gbps, node.compute_units);
+        node.memory_bandwidth_gbps = memory_bandwidth_gbps;
+        node.compute_units = compute_units;
+        if self.validate_hardware(&mut node).is_err() {
+            (node.memory_bandwidth_gbps, node.compute_units) = previous;
+        }
+        let memory_bandwidth_gbps = node.memory_bandwidth_gbps;
+        let compute_units = node.compute_units;
+
+        let policy = self.config.capability_regression_policy;
+        let regressed = policy != RegressionPolicy::Ignore && {
+            let mut stats = self
+                .node_stats
+                .entry(id.to_string())
+                .or_insert_with(|| NodeStats::new(self.clock.now(), &node));
+
+            let floor = 1.0 - self.config.capability_regression_ratio.clamp(0.0, 1.0);
+            let bw_dropped = stats.baseline_bandwidth_gbps > 0.0
+                && memory_bandwidth_gbps < stats.baseline_bandwidth_gbps * floor;
+            let cu_dropped = stats.baseline_compute_units > 0
+                && (compute_units as f64) < stats.baseline_compute_units as f64 * floor;
+
+            if (bw_dropped || cu_dropped) && !stats.capability_regressed {
+                warn!(
+                    "[Regression] {}: bw {:.1} -> {:.1} GB/s, cu {} -> {}",
+                    id,
+                    stats.baseline_bandwidth_gbps,
+                    memory_bandwidth_gbps,
+                    stats.baseline_compute_units,
+                    compute_units
+                );
+                stats.capability_regressed = true;
+            }
+            stats.capability_regressed
+        };
+
+        if regressed && policy == RegressionPolicy::Quarantine {
+            node.health_state = HealthState::Quarantined;
+            node.is_quarantined = true;
+        }
+        self.refresh_tier(&mut node);
+        Some(regressed)
+    }
+
+    // 조사 완료 후: 현재 값을 새 기준으로 삼고 퇴행 표시 해제
+    pub fn clear_capability_regression(&self, id: &str) -> bool {
+        let Some(node) = self.nodes.get(id) else {
+            return false;
+        };
+        let Some(mut stats) = self.node_stats.get_mut(id) else {
+            return false;
+        };
+        stats.baseline_bandwidth_gbps = node.memory_bandwidth_gbps;
+        stats.baseline_compute_units = node.compute_units;
+        stats.capability_regressed = false;
+        info!("[Regression] {} cleared, new baseline recorded", id);
+        true
+    }
+
+    pub fn regressed_nodes(&self) -> Vec<String> {
+        self.node_stats
+            .iter()
+            .filter(|e| e.value().capability_regressed)
+            .map(|e| e.key().clone())
+            .collect()
+    }
+
+    fn is_held_for_regression(&self, id: &str) -> bool {
+        self.config.capability_regression_policy == RegressionPolicy::Quarantine
+            && self
+                .node_stats
+                .get(id)
+                .is_some_and(|s| s.capability_regressed)
+    }
+
+    // 폐기된 노드가 계속 Heartbeat를 보내도 로그가 넘치지 않도록 id별로 제한
+    fn log_unknown_heartbeat(&self, source: &str, id: &str) {
+        let now = self.clock.now();
+        let interval = self.config.unknown_node_log_interval_secs;
+
+        match self.unknown_heartbeats.entry(id.to_string()) {
+            dashmap::mapref::entry::Entry::Occupied(mut e) => {
+                let log = e.get_mut();
+                if now
+                    .saturating_duration_since(log.last_logged_at)
+                    .as_secs_f64()
+                    < interval
+                {
+                    log.suppressed += 1;
+                    return;
+                }
+                warn!(
+                    "[Scheduler] {}: unknown node_id={} ({} suppressed)",
+                    source, id, log.suppressed
+                );
+                log.last_logged_at = now;
+                log.suppressed = 0;
+            }
+            dashmap::mapref::entry::Entry::Vacant(e) => {
+                warn!("[Scheduler] {}: unknown node_id={}", source, id);
+                e.insert(UnknownHeartbeatLog {
+                    last_logged_at: now,
+                    suppressed: 0,
+                });
+            }
+        }
+    }
+
+    fn prune_unknown_heartbeat_logs(&self) {
+        let now = self.clock.now();
+        let interval = self.config.unknown_node_log_interval_secs;
+        self.unknown_heartbeats.retain(|_, log| {
+            log.suppressed > 0
+                || now
+                    .saturating_duration_since(log.last_logged_at)
+                    .as_secs_f64()
+                    < interval
+        });
+    }
+
+    fn update_health_state(&self, node: &mut NodeContext) {
+        let before = node.health_state;
+        self.evaluate_health_state(node);
+
+        // Healthy <-> Suspect를 오가는 노드는 누적 Suspect 시간으로 판단해 격리
+        if self.track_suspect_time(node) && self.config.chronic_suspect_quarantine {
+            node.health_state = HealthState::Quarantined;
+            node.is_quarantined = true;
+        }
+        self.accumulate_uptime(node);
+        self.sync_offline_tier(node);
+
+        self.note_health_change(&node.node_id, before, node.health_state);
+        metrics::observe_effective_opi(self.calculate_effective_opi(node));
+    }
+
+    // 타임아웃으로 격리된 노드는 Offline으로 표시하고, 다시 응답하면 하드웨어 기준 Tier로 복귀
+    fn sync_offline_tier(&self, node: &mut NodeContext) {
+        if self.is_timed_out(node) {
+            if node.current_tier != NodeTier::Offline {
+                info!(
+                    "[Tier Change] {}: {:?} -> Offline (no heartbeat)",
+                    node.node_id, node.current_tier
+                );
+                self.note_tier_change(&node.node_id, &node.current_tier, &NodeTier::Offline);
+                node.current_tier = NodeTier::Offline;
+            }
+        } else if node.current_tier == NodeTier::Offline
+            && !node.capability_invalid
+            && matches!(
+                node.health_state,
+                HealthState::Healthy | HealthState::Degraded
+            )
+        {
+            self.refresh_tier(node);
+        }
+    }
+
+    fn is_timed_out(&self, node: &NodeContext) -> bool {
+        let since_seen = self
+            .clock
+            .now()
+            .saturating_duration_since(node.last_seen)
+            .as_secs_f64();
+        since_seen > self.health_thresholds.quarantine_after_secs && !self.in_revive_grace(node)
+    }
+
+    fn in_revive_grace(&self, node: &NodeContext) -> bool {
+        let now = self.clock.now();
+        self.node_stats
+            .get(&node.node_id)
+            .and_then(|s| s.revived_until)
+            .is_some_and(|until| now < until)
+    }
+
+    // 직전 평가 이후 경과 시간을 정상 상태일 때만 더한다. 격리되면 처음부터 다시 쌓는다
+    fn accumulate_uptime(&self, node: &mut NodeContext) {
+        let now = self.clock.now();
+        let Some(mut stats) = self.node_stats.get_mut(&node.node_id) else {
+            return;
+        };
+        let elapsed = stats
+            .uptime_marked_at
+            .map(|t| now.saturating_duration_since(t).as_secs_f64())
+            .unwrap_or(0.0);
+        stats.uptime_marked_at = Some(now);
+
+        match node.health_state {
+            HealthState::Healthy | HealthState::Degraded => node.healthy_uptime_secs += elapsed,
+            HealthState::Quarantined => node.healthy_uptime_secs = 0.0,
+            HealthState::Suspect => {}
+        }
+    }
+
+    // 1.0 ~ 1.0 + reliability_bonus_max, reliability_full_uptime_secs에서 상한
+    fn reliability_modifier(&self, node: &NodeContext) -> f64 {
+        let max = self.config.reliability_bonus_max.max(0.0);
+        let full = self.config.reliability_full_uptime_secs;
+        if max <= 0.0 || full <= 0.0 {
+            return 1.0;
+        }
+        1.0 + max * (node.healthy_uptime_secs / full).clamp(0.0, 1.0)
+    }
+
+    pub fn healthy_uptime_secs(&self, id: &str) -> Option<f64> {
+        self.nodes.get(id).map(|n| n.healthy_uptime_secs)
+    }
+
+    fn evaluate_health_state(&self, node: &mut NodeContext) {
+        use HealthState::*;
+
+        let now = self.clock.now();
+        let since_seen = now.saturating_duration_since(node.last_seen).as_secs_f64();
+
+        let t = &self.health_thresholds;
+
+        // 관리자 격리가 다른 모든 판정보다 우선
+        if self
+            .node_stats
+            .get(&node.node_id)
+            .is_some_and(|s| s.admin_quarantined)
+        {
+            node.health_state = Quarantined;
+            node.is_quarantined = true;
+            return;
+        }
+
+        // 비상 복귀 유예 중에는 Heartbeat가 없어도 Degraded로 둔다 (다시 오면 아래 일반 판정)
+        if since_seen > t.suspect_after_secs && self.in_revive_grace(node) {
+            node.health_state = Degraded;
+            node.is_quarantined = false;
+            return;
+        }
+
+        // 하드 타임아웃 기준
+        if since_seen > t.quarantine_after_secs {
+            node.health_state = Quarantined;
+            node.is_quarantined = true;
+            return;
+        } else if since_seen > t.suspect_after_secs {
+            node.health_state = Suspect;
+            // 스케줄링에서는 제외하되, 일단 완전 격리는 아님
+            return;
+        }
+
+        // 명령 타임아웃이 연달아 난 노드는 Heartbeat가 와도 격리 유지 (try_rehabilitate_node로만 해제)
+        if node.failure_count >= t.max_consecutive_failures {
+            node.health_state = Quarantined;
+            node.is_quarantined = true;
+            return;
+        }
+
+        // 재활 중인 노드는 정상 보고가 쌓일 때까지 Suspect (스케줄링 제외)
+        if self
+            .node_stats
+            .get(&node.node_id)
+            .is_some_and(|s| s.probation_remaining > 0)
+        {
+            node.health_state = Suspect;
+            node.is_quarantined = false;
+            return;
+        }
+
+        // 하드웨어 퇴행으로 격리된 노드는 응답이 있어도 해제하지 않음
+        if self.is_held_for_regression(&node.node_id) {
+            node.health_state = Quarantined;
+            node.is_quarantined = true;
+            return;
+        }
+
+        // RTT 기반 상태 (Heartbeat는 오고 있다고 가정)
+        if node.net_rtt_ema_ms > t.degraded_rtt_ms {
+            node.health_state = Degraded;
+            node.is_quarantined = false;
+        } else {
+            node.health_state = Healthy;
+            node.is_quarantined = false;
+        }
+    }
+
+    // Suspect 구간을 기록하고, 최근 window 안의 누적 Suspect 시간이 한도를 넘었는지 반환
+    fn track_suspect_time(&self, node: &NodeContext) -> bool {
+        let now = self.clock.now();
+        let window = Duration::from_secs_f64(self.config.chronic_suspect_window_secs.max(0.0));
+        let Some(mut stats) = self.node_stats.get_mut(&node.node_id) else {
+            return false;
+        };
+
+        // 재활 중 Suspect는 의도된 상태이므로 불안정 시간으로 세지 않는다
+        let is_suspect =
+            node.health_state == HealthState::Suspect && stats.probation_remaining == 0;
+        // 판정 직후(격리 승격 전) 상태 기준으로 구간을 열고 닫는다
+        match (stats.suspect_since, is_suspect) {
+            (None, true) => stats.suspect_since = Some(now),
+            (Some(start), false) => {
+                stats.suspect_intervals.push_back((start, now));
+                stats.suspect_since = None;
+            }
+            _ => {}
+        }
+
+        let window_start = now.checked_sub(window).unwrap_or(now);
+        while stats
+            .suspect_intervals
+            .front()
+            .is_some_and(|(_, end)| *end < window_start)
+        {
+            stats.suspect_intervals.pop_front();
+        }
+
+        let overlap = |start: Instant, end: Instant| {
+            end.saturating_duration_since(start.max(window_start))
+                .as_secs_f64()
+        };
+        let total: f64 = stats
+            .suspect_intervals
+            .iter()
+            .map(|(s, e)| overlap(*s, *e))
+            .sum::<f64>()
+            + stats.suspect_since.map(|s| overlap(s, now)).unwrap_or(0.0);
+
+        let chronic = total > self.config.chronic_suspect_secs;
+        if chronic && !stats.chronically_flaky {
+            warn!(
+                "[Health] {} flapping: {:.0}s in Suspect over last {:.0}s",
+                node.node_id,
+                total,
+                window.as_secs_f64()
+            );
+        }
+        stats.chronically_flaky = chronic;
+        chronic
+    }
+
+    pub fn flaky_nodes(&self) -> Vec<String> {
+        self.node_stats
+            .iter()
+            .filter(|e| e.value().chronically_flaky)
+            .map(|e| e.key().clone())
+            .collect()
+    }
+
+    // 격리 해제 경로:
+    //   Quarantined --(최근 Heartbeat 있음, 퇴행 보류 아님)--> Suspect (재활, 스케줄링 제외)
+    //   Suspect(재활) --(정상 상태 보고 probation_clean_updates회)--> Healthy/Degraded
+    //   재활 중 Heartbeat 끊김/실패 누적 --> 다시 Quarantined
+    // 연속 실패로 격리된 노드는 이 경로로만 복귀한다 (타임아웃 격리는 Heartbeat로 자동 복귀)
+    pub fn try_rehabilitate_node(&self, id: &str) -> Result<HealthState, RehabilitationError> {
+        let mut node = self
+            .nodes
+            .get_mut(id)
+            .ok_or_else(|| RehabilitationError::UnknownNode(id.to_string()))?;
+        if node.health_state != HealthState::Quarantined {
+            return Err(RehabilitationError::NotQuarantined {
+                node_id: id.to_string(),
+                state: node.health_state,
+            });
+        }
+
+        let since_seen = self
+            .clock
+            .now()
+            .saturating_duration_since(node.last_seen)
+            .as_secs_f64();
+        if since_seen > self.health_thresholds.suspect_after_secs {
+            return Err(RehabilitationError::NoFreshHeartbeat {
+                node_id: id.to_string(),
+                since_seen_secs: since_seen,
+            });
+        }
+        if self.is_held_for_regression(id) {
+            return Err(RehabilitationError::HeldForRegression(id.to_string()));
+        }
+
+        node.failure_count = 0;
+        if let Some(mut stats) = self.node_stats.get_mut(id) {
+            stats.probation_remaining = self.config.probation_clean_updates.max(1);
+        }
+        self.update_health_state(&mut node);
+        info!(
+            "[Health] {} on probation ({} clean update(s) required)",
+            id,
+            self.config.probation_clean_updates.max(1)
+        );
+        Ok(node.health_state)
+    }
+
+    // 유지보수 예정 노드: 새 Shard/Offload 배정만 막고 격리/퇴출은 하지 않는다
+    pub fn drain_node(&self, id: &str) -> bool {
+        // nodes 잠금 전에 세어 assignments와의 잠금 순서가 엇갈리지 않게 한다
+        let in_flight = self
+            .assignments
+            .read()
+            .values()
+            .filter(|a| a.node_id == id)
+            .count();
+        let Some(mut node) = self.nodes.get_mut(id) else {
+            return false;
+        };
+        if !node.is_draining {
+            node.is_draining = true;
+            info!(
+                "[Drain] {} draining ({} in-flight shard(s) will finish)",
+                id, in_flight
+            );
+        }
+        true
+    }
+
+    pub fn undrain_node(&self, id: &str) -> bool {
+        let Some(mut node) = self.nodes.get_mut(id) else {
+            return false;
+        };
+        if node.is_draining {
+            node.is_draining = false;
+            info!("[Drain] {} back in service", id);
+        }
+        true
+    }
+
+    // 운영자 디버깅용: Heartbeat 없이 지금 시각 기준으로 한 노드만 즉시 재평가
+    pub fn reevaluate_node(&self, id: &str) -> Option<HealthState> {
+        let mut node = self.nodes.get_mut(id)?;
+        let before = node.health_state;
+        self.update_health_state(&mut node);
+        if node.health_state != before {
+            info!(
+                "[Health] {} re-evaluated: {:?} -> {:?}",
+                id, before, node.health_state
+            );
+        }
+        Some(node.health_state)
+    }
+
+    // Heartbeat가 끊긴 노드는 스스로 상태를 바꿀 기회가 없으므로 주기적으로 재평가.
+    // 상태가 바뀐 노드만 (node_id, 이전, 이후)로 반환. tick 없이 따로 돌릴 때 예:
+    //
+    //   let mut interval = tokio::time::interval(Duration::from_secs(5));
+    //   loop {
+    //       interval.tick().await;
+    //       for (id, from, to) in scheduler.sweep_health() {
+    //           info!("[Health] {} {:?} -> {:?}", id, from, to);
+    //       }
+    //   }
+    pub fn sweep_health(&self) -> Vec<(String, HealthState, HealthState)> {
+        let mut changed = Vec::new();
+        for mut entry in self.nodes.iter_mut() {
+            let before = entry.value().health_state;
+            self.update_health_state(entry.value_mut());
+            let after = entry.value().health_state;
+            if before != after {
+                changed.push((entry.key().clone(), before, after));
+            }
+        }
+        changed
+    }
+
+    // 노드 시계는 참고용일 뿐 last_seen은 항상 마스터 도착 시각으로 기록한다.
+    // 타임스탬프가 뒤로 가면(NTP 보정 등) 무시하고 새 값을 기준으로 다시 잡는다 (로그는 노드당 한 번)
+    pub fn observe_heartbeat_timestamp(&self, id: &str, sent_at_unix_ms: u64) -> bool {
+        let Some(mut stats) = self.node_stats.get_mut(id) else {
+            return false;
+        };
+
+        let monotonic = match stats.last_reported_ts_ms {
+            Some(prev) if sent_at_unix_ms < prev => {
+                if stats.clock_regressions == 0 {
+                    warn!(
+                        "[Heartbeat] {} clock went backward by {}ms; using arrival time",
+                        id,
+                        prev - sent_at_unix_ms
+                    );
+                }
+                stats.clock_regressions += 1;
+                false
+            }
+            _ => true,
+        };
+        stats.last_reported_ts_ms = Some(sent_at_unix_ms);
+        monotonic
+    }
+
+    fn record_heartbeat_stats(
+        &self,
+        node: &NodeContext,
+        rtt_ms: f64,
+        prev_rtt_ema: f64,
+        gamma: f64,
+    ) {
+        let now = self.clock.now();
+        let mut stats = self
+            .node_stats
+            .entry(node.node_id.clone())
+            .or_insert_with(|| NodeStats::new(now, node));
+
+        let capacity = self.config.load_history_capacity;
+        if capacity > 0 {
+            while stats.load_history.len() >= capacity {
+                stats.load_history.pop_front();
+            }
+            stats.load_history.push_back(LoadSample {
+                at: now,
+                cpu_load: node.cpu_load,
+                gpu_load: node.gpu_load,
+            });
+        }
+
+        if rtt_ms > 0.0 {
+            stats.rtt_samples = stats.rtt_samples.saturating_add(1);
+        }
+        stats.probation_remaining = stats.probation_remaining.saturating_sub(1);
+        if rtt_ms > 0.0 && prev_rtt_ema > 0.0 {
+            let dev = (rtt_ms - prev_rtt_ema).abs();
+            stats.rtt_jitter_ema_ms = gamma * dev + (1.0 - gamma) * stats.rtt_jitter_ema_ms;
+        }
+
+        if let Some(prev) = stats.last_heartbeat_at {
+            let interval = now.saturating_duration_since(prev).as_secs_f64();
+            if stats.heartbeat_interval_ema_secs <= 0.0 {
+                stats.heartbeat_interval_ema_secs = interval;
+            } else {
+                let dev = (interval - stats.heartbeat_interval_ema_secs).abs();
+                stats.heartbeat_jitter_ema_secs =
+                    gamma * dev + (1.0 - gamma) * stats.heartbeat_jitter_ema_secs;
+                stats.heartbeat_interval_ema_secs =
+                    gamma * interval + (1.0 - gamma) * stats.heartbeat_interval_ema_secs;
+            }
+        }
+        stats.last_heartbeat_at = Some(now);
+    }
+
+    // 오래된 샘플부터 반환
+    pub fn node_load_history(&self, id: &str) -> Vec<LoadSample> {
+        self.node_stats
+            .get(id)
+            .map(|s| s.load_history.iter().copied().collect())
+            .unwrap_or_default()
+    }
+
+    // 오래된 결정부터 반환
+    pub fn node_history(&self, id: &str) -> Vec<HistoryEntry> {
+        self.node_stats
+            .get(id)
+            .map(|s| s.decision_history.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    // ---------------- Trust Score ----------------
+    // RTT 안정성 × 실패 이력 × Heartbeat 규칙성 × 가동 시간 (각 0~1)
+
+    fn calculate_trust_score(&self, node: &NodeContext) -> f64 {
+        let Some(stats) = self.node_stats.get(&node.node_id) else {
+            return 1.0;
+        };
+
+        let rtt_stability = if node.net_rtt_ema_ms > 0.0 {
+            1.0 / (1.0 + stats.rtt_jitter_ema_ms / node.net_rtt_ema_ms)
+        } else {
+            1.0
+        };
+
+        let failure = 1.0 / (1.0 + node.failure_count as f64);
+
+        let regularity = if stats.heartbeat_interval_ema_secs > 0.0 {
+            1.0 / (1.0 + stats.heartbeat_jitter_ema_secs / stats.heartbeat_interval_ema_secs)
+        } else {
+            1.0
+        };
+
+        // 갓 들어온 노드는 0.5부터 시작해 trust_full_uptime_secs에 걸쳐 1.0까지
+        let uptime = self
+            .clock
+            .now()
+            .saturating_duration_since(stats.registered_at)
+            .as_secs_f64();
+        let full = self.config.trust_full_uptime_secs.max(1.0);
+        let maturity = 0.5 + 0.5 * (uptime / full).min(1.0);
+
+        (rtt_stability * failure * regularity * maturity).clamp(0.0, 1.0)
+    }
+
+    pub fn trust_score(&self, id: &str) -> Option<f64> {
+        let node = self.nodes.get(id)?;
+        Some(self.calculate_trust_score(&node))
+    }
+
+    fn trust_modifier(&self, node: &NodeContext) -> f64 {
+        let w = self.config.trust_weight.clamp(0.0, 1.0);
+        if w <= 0.0 {
+            return 1.0;
+        }
+        (1.0 - w) + w * self.calculate_trust_score(node)
+    }
+
+    fn calculate_net_factor(&self, node: &NodeContext) -> f64 {
+        let base_rtt_ms = 10.0_f64; // "정상" LAN 기준
+        let max_penalty = 10.0_f64; // 최대 10배 페널티
+
+        let rtt = if node.net_rtt_ema_ms <= 0.0 {
+            base_rtt_ms
+        } else {
+            node.net_rtt_ema_ms
+        };
+
+        let raw = (rtt / base_rtt_ms).clamp(1.0, max_penalty);
+
+        // 표본이 부족한 RTT는 최선값으로 믿지 않고 중간값 이상으로 본다 (신규 노드 몰림 방지).
+        // RTT를 아예 보고하지 않는 노드(update_node_status 경로, samples == 0)는 수렴할 일이
+        // 없으므로 기준값으로 둔다
+        let samples = self
+            .node_stats
+            .get(&node.node_id)
+            .map(|s| s.rtt_samples)
+            .unwrap_or(0);
+        if node.net_rtt_ema_ms > 0.0 && samples > 0 && samples < self.config.rtt_min_samples {
+            raw.max(self.config.unconverged_net_factor.clamp(1.0, max_penalty))
+        } else {
+            raw
+        }
+    }
+
+    fn calculate_load_factor(&self, node: &NodeContext, profile: ResourceProfile) -> f64 {
+        let cpu = node.cpu_load.clamp(0.0, 1.0);
+        let gpu = node.gpu_load.clamp(0.0, 1.0);
+        let primary = self.config.profile_primary_weight.clamp(0.0, 1.0);
+        let load = match (profile, self.config.load_blend) {
+            (ResourceProfile::CpuBound, _) => primary * cpu + (1.0 - primary) * gpu,
+            (ResourceProfile::GpuBound, _) => primary * gpu + (1.0 - primary) * cpu,
+            (ResourceProfile::Combined, LoadBlend::Max) => gpu.max(cpu),
+            (ResourceProfile::Combined, LoadBlend::Weighted { cpu_weight }) => {
+                let w = cpu_weight.clamp(0.0, 1.0);
+                w * cpu + (1.0 - w) * gpu
+            }
+        };
+        1.0 - load
+    }
+
+    // 작업이 쓰는 자원이 포화된 노드는 제외 (GPU 작업에는 CPU 포화가 치명적이지 않음)
+    fn is_overloaded(&self, node: &NodeContext, profile: ResourceProfile) -> bool {
+        let t = self.node_thresholds(&node.current_tier);
+        match profile {
+            ResourceProfile::Combined => node.cpu_load > t.cpu_max || node.gpu_load > t.gpu_max,
+            ResourceProfile::CpuBound => node.cpu_load > t.cpu_max,
+            ResourceProfile::GpuBound => node.gpu_load > t.gpu_max,
+        }
+    }
+
+    pub fn node_thresholds(&self, tier: &NodeTier) -> OverloadThresholds {
+        self.config
+            .tier_thresholds
+            .get(tier)
+            .copied()
+            .unwrap_or(self.config.node_overload)
+    }
+
+    fn calculate_effective_opi(&self, node: &NodeContext) -> f64 {
+        self.profiled_effective_opi(node, ResourceProfile::Combined)
+    }
+
+    fn profiled_effective_opi(&self, node: &NodeContext, profile: ResourceProfile) -> f64 {
+        use HealthState::*;
+
+        if node.is_quarantined
+            || node.capability_invalid
+            || matches!(node.health_state, Quarantined | Suspect)
+        {
+            return 0.0;
+        }
+
+        let hw = self.calculate_raw_opi(node);
+        let net = self.calculate_net_factor(node);
+        let load_factor = self.calculate_load_factor(node, profile);
+        let modifiers = self.score_modifiers(node);
+
+        // 음수 점수는 정규화/정렬을 깨뜨리므로 0으로 보정 (0 = 스케줄링 불가)
+        ((hw / net) * load_factor * modifiers).max(0.0)
+    }
+
+    // 하드웨어/네트워크/부하 외의 배수 (부스트, 반납 페널티, 신뢰도, 가동 이력, 배터리, 예약)
+    fn score_modifiers(&self, node: &NodeContext) -> f64 {
+        self.active_boost_multiplier(&node.node_id)
+            * self.rejection_penalty_modifier(&node.node_id)
+            * self.trust_modifier(node)
+            * self.reliability_modifier(node)
+            * self.battery_modifier(node)
+            * self.reservation_modifier(node)
+            / self
+                .config
+                .network_penalties
+                .for_kind(node.network_kind)
+                .max(1.0)
+    }
+
+    // "왜 내 노드가 선택되지 않았나" 디버깅용. effective는 실제 스케줄링 값과 동일
+    pub fn explain_node_opi(&self, id: &str) -> Option<OpiBreakdown> {
+        let node = self.nodes.get(id)?;
+        Some(OpiBreakdown {
+            raw_hw: self.calculate_raw_opi(&node),
+            net_factor: self.calculate_net_factor(&node),
+            load_factor: self.calculate_load_factor(&node, ResourceProfile::Combined),
+            modifiers: self.score_modifiers(&node),
+            effective: self.calculate_effective_opi(&node),
+            limiting_factor: self.calculate_limiting_factor(&node),
+        })
+    }
+
+    // 방금 배정된 노드는 Heartbeat로 부하가 올라오기 전까지 순위를 미리 낮춘다
+    fn reservation_modifier(&self, node: &NodeContext) -> f64 {
+        let penalty = self.config.reservation_penalty.max(0.0);
+        1.0 / (1.0 + penalty * node.pending_reservations as f64)
+    }
+
+    // 배터리로 동작 중인 모바일 노드는 후순위 (데스크톱급 Tier1/2는 영향 없음)
+    fn battery_modifier(&self, node: &NodeContext) -> f64 {
+        if node.current_tier != NodeTier::Tier3Mobile || node.is_charging {
+            return 1.0;
+        }
+        if self.config.exclude_mobile_on_battery {
+            return 0.0;
+        }
+        self.config.battery_penalty_multiplier.clamp(0.0, 1.0)
+    }
+
+    fn calculate_limiting_factor(&self, node: &NodeContext) -> LimitingFactor {
+        use HealthState::*;
+
+        if node.is_quarantined
+            || node.capability_invalid
+            || matches!(node.health_state, Quarantined | Suspect)
+        {
+            return LimitingFactor::Health;
+        }
+
+        // 두 항목 모두 1.0(페널티 없음)에서 얼마나 깎는지로 비교
+        let net_multiplier = 1.0 / self.calculate_net_factor(node);
+        let load_multiplier = self.calculate_load_factor(node, ResourceProfile::Combined);
+        if net_multiplier >= 1.0 && load_multiplier >= 1.0 {
+            LimitingFactor::None
+        } else if net_multiplier < load_multiplier {
+            LimitingFactor::Network
+        } else {
+            LimitingFactor::Load
+        }
+    }
+
+    pub fn limiting_factor(&self, id: &str) -> Option<LimitingFactor> {
+        let node = self.nodes.get(id)?;
+        Some(self.calculate_limiting_factor(&node))
+    }
+
+    fn is_schedulable_opi(score: f64) -> bool {
+        score > 0.0
+    }
+
+    pub fn effective_opi(&self, id: &str) -> Option<f64> {
+        let node = self.nodes.get(id)?;
+        Some(self.calculate_effective_opi(&node))
+    }
+
+    fn snapshot_node(&self, node: &NodeContext, now: Instant) -> NodeSnapshot {
+        NodeSnapshot {
+            node_id: node.node_id.clone(),
+            current_tier: node.current_tier.clone(),
+            health_state: node.health_state,
+            net_rtt_ema_ms: node.net_rtt_ema_ms,
+            effective_opi: self.calculate_effective_opi(node),
+            cpu_load: node.cpu_load,
+            gpu_load: node.gpu_load,
+            is_quarantined: node.is_quarantined,
+            zone: node.zone.clone(),
+            trust_score: self.calculate_trust_score(node),
+            limiting_factor: self.calculate_limiting_factor(node),
+            healthy_uptime_secs: node.healthy_uptime_secs,
+            last_seen_age_secs: now.saturating_duration_since(node.last_seen).as_secs_f64(),
+            is_draining: node.is_draining,
+            pending_reservations: node.pending_reservations,
+        }
+    }
+
+    pub fn get_node(&self, id: &str) -> Option<NodeSnapshot> {
+        let node = self.nodes.get(id)?;
+        Some(self.snapshot_node(&node, self.clock.now()))
+    }
+
+    // node_id 순으로 정렬 (대시보드 표시 순서 고정)
+    pub fn list_nodes(&self) -> Vec<NodeSnapshot> {
+        let now = self.clock.now();
+        let mut nodes: Vec<NodeSnapshot> = self
+            .nodes
+            .iter()
+            .map(|e| self.snapshot_node(e.value(), now))
+            .collect();
+        nodes.sort_by(|a, b| a.node_id.cmp(&b.node_id));
+        nodes
+    }
+
+    // 대시보드용: 맵을 한 번만 순회해서 전체 노드 점수 계산
+    pub fn effective_opi_all(&self) -> HashMap<String, f64> {
+        self.nodes
+            .iter()
+            .map(|e| (e.key().clone(), self.calculate_effective_opi(e.value())))
+            .collect()
+    }
+
+    // 컨테이너와 무관한 전체 순위표: 스케줄링 가능한 노드 중 effective OPI 상위 n개
+    pub fn top_nodes(&self, n: usize) -> Vec<(String, f64)> {
+        let mut scored: Vec<(String, f64, f64)> = self
+            .nodes
+            .iter()
+            .filter_map(|e| {
+                self.score_candidate(e.value(), ResourceProfile::Combined)
+                    .map(|(id, score, _)| (id, score, e.value().net_rtt_ema_ms))
+            })
+            .collect();
+        scored.sort_by(|a, b| {
+            b.1.total_cmp(&a.1)
+                .then_with(|| Self::tie_break((&a.0, a.2), (&b.0, b.2)))
+        });
+        scored.truncate(n);
+        scored
+            .into_iter()
+            .map(|(id, score, _)| (id, score))
+            .collect()
+    }
+
+    // ---------------- Priority Boost ----------------
+
+    // 특정 노드 테스트 / 신규 노드 워밍업용으로 effective OPI에 배수를 건다
+    pub fn set_priority_boost(&self, id: &str, multiplier: f64, expires_at: Option<Instant>) {
+        if !multiplier.is_finite() || multiplier <= 0.0 {
+            warn!(
+                "[Boost] Ignored invalid multiplier {} for node {}",
+                multiplier, id
+            );
+            return;
+        }
+        if !self.nodes.contains_key(id) {
+            warn!("[Boost] set_priority_boost: unknown node_id={}", id);
+            return;
+        }
+
+        info!(
+            "[Boost] {} x{:.2} (expires: {:?})",
+            id, multiplier, expires_at
+        );
+        self.priority_boosts.insert(
+            id.to_string(),
+            PriorityBoost {
+                multiplier,
+                expires_at,
+            },
+        );
+    }
+
+    pub fn clear_priority_boost(&self, id: &str) {
+        self.priority_boosts.remove(id);
+    }
+
+    fn active_boost_multiplier(&self, id: &str) -> f64 {
+        let now = self.clock.now();
+        match self.priority_boosts.get(id) {
+            Some(b) if b.is_active(now) => b.multiplier,
+            _ => 1.0,
+        }
+    }
+
+    fn rejection_penalty_modifier(&self, id: &str) -> f64 {
+        let now = self.clock.now();
+        match self.rejection_penalties.get(id) {
+            Some(until) if now < *until => self.config.rejection_penalty_multiplier,
+            _ => 1.0,
+        }
+    }
+
+    fn expire_priority_boosts(&self) -> Vec<String> {
+        let now = self.clock.now();
+        let mut expired = Vec::new();
+        self.priority_boosts.retain(|id, b| {
+            let alive = b.is_active(now);
+            if !alive {
+                expired.push(id.clone());
+            }
+            alive
+        });
+        for id in &expired {
+            info!("[Boost] Expired: {}", id);
+        }
+        expired
+    }
+
+    // 샤딩 판단에 그대로 쓰이는 값이므로 [0, 1]로 보정해서 저장. NaN 거부 시 false 반환 (이전 값 유지)
+    pub fn update_master_status(&self, cpu: f64, gpu: f64, vram_ratio: f64) -> bool {
+        let inputs = [("cpu", cpu), ("gpu", gpu), ("vram_ratio", vram_ratio)];
+        if self.config.reject_nan_master_status && inputs.iter().any(|(_, v)| v.is_nan()) {
+            warn!(
+                "[Master] Rejected status with NaN: cpu={} gpu={} vram={}",
+                cpu, gpu, vram_ratio
+            );
+            return false;
+        }
+
+        let [cpu, gpu, vram_ratio] = inputs.map(|(name, v)| {
+            let clamped = if v.is_nan() { 0.0 } else { v.clamp(0.0, 1.0) };
+            if clamped != v {
+                warn!(
+                    "[Master] {} out of range ({}), clamped to {}",
+                    name, v, clamped
+                );
+            }
+            clamped
+        });
+
+        let mut status = self.server_status.lock();
+        status.cpu_load = cpu;
+        status.gpu_load = gpu;
+        status.vram_usage_ratio = vram_ratio;
+        true
+    }
+
+    pub fn update_master_workloads(&self, workloads: Vec<MasterWorkload>) {
+        self.server_status.lock().workloads = workloads;
+    }
+
+    pub fn set_master_load_domain(&self, domain: Option<String>) {
+        self.server_status.lock().load_domain = domain;
+    }
+
+    // ---------------- Smart Sharding ----------------
+
+    pub fn check_server_overload_and_shard(&self) -> ShardOutcome {
+        let plan = {
+            let status = self.server_status.lock();
+            self.build_sharding_plan(&status, true)
+        };
+
+        let mut commands = Vec::new();
+        let mut chosen = HashMap::new();
+        for shard in &plan.shards {
+            let cmd = self.create_shard_command(&shard.container_id);
+            self.track_assignment(&shard.node_id, &cmd);
+            self.note_task_sharded(&cmd.task_id, vec![shard.node_id.clone()]);
+            commands.push((shard.node_id.clone(), cmd));
+            chosen.insert(
+                shard.node_id.clone(),
+                HistoryEvent::ChosenForShard {
+                    container_id: shard.container_id.clone(),
+                },
+            );
+        }
+        if plan.cause.is_some() {
+            self.record_decision_pass(plan.evaluated, &chosen);
+        }
+
+        ShardOutcome {
+            tripped: plan.tripped,
+            commands,
+        }
+    }
+
+    // 드라이런: 지금 부하라면 어디로 무엇을 보낼지만 계산. 명령 생성/할당 기록/비상 복귀 없음
+    pub fn plan_sharding(&self) -> ShardingPlan {
+        let status = self.server_status.lock();
+        self.build_sharding_plan(&status, false)
+    }
+
+    fn aggregate_candidate_opi(&self, candidates: &[String]) -> f64 {
+        candidates
+            .iter()
+            .filter_map(|id| self.nodes.get(id))
+            .map(|n| self.calculate_effective_opi(&n))
+            .filter(|s| s.is_finite())
+            .sum()
+    }
+
+    fn admits_sharding(&self, candidate_count: usize, aggregate_opi: f64) -> bool {
+        candidate_count >= self.config.min_viable_candidates
+            && aggregate_opi >= self.config.min_aggregate_opi
+    }
+
+    // live=false면 상태를 건드리지 않는다 (capacity emergency 처리 생략)
+    fn build_sharding_plan(&self, status: &ServerStatus, live: bool) -> ShardingPlan {
+        let thresholds = self.thresholds_for(status);
+        let mut plan = ShardingPlan {
+            tripped: Self::tripped_thresholds(status, &thresholds),
+            cause: Self::overload_cause(status, &thresholds),
+            ..Default::default()
+        };
+        let Some(cause) = plan.cause else {
+            return plan;
+        };
+
+        let (mut candidates, mut evaluated) = self.find_smart_candidates_evaluated();
+        if live {
+            if candidates.is_empty() && self.handle_capacity_emergency().is_some() {
+                (candidates, evaluated) = self.find_smart_candidates_evaluated();
+            } else if !candidates.is_empty() {
+                self.capacity_emergency
+                    .store(false, AtomicOrdering::Relaxed);
+            }
+        }
+        plan.evaluated = evaluated;
+        plan.candidate_count = candidates.len();
+        plan.aggregate_opi = self.aggregate_candidate_opi(&candidates);
+        if !candidates.is_empty() && !self.admits_sharding(plan.candidate_count, plan.aggregate_opi)
+        {
+            if live {
+                warn!(
+                    "[Shard] Suppressed: {} candidate(s) with aggregate OPI {:.1} (need {} / {:.1})",
+                    plan.candidate_count,
+                    plan.aggregate_opi,
+                    self.config.min_viable_candidates,
+                    self.config.min_aggregate_opi
+                );
+            }
+            plan.suppressed = true;
+            return plan;
+        }
+
+        // 마스터 워크로드 정보가 없으면 기존처럼 일반 작업을 내보냄.
+        // 실제 노드 수보다 많은 샤드를 만들지 않도록 후보 수로도 제한
+        let targets = Self::shed_targets(&status.workloads, cause);
+        let mut fanout = self.config.max_shard_fanout.min(candidates.len());
+        if !targets.is_empty() {
+            fanout = fanout.min(targets.len());
+        }
+
+        // 대상 컨테이너를 레지스트리에서 찾을 수 있으면 VRAM 등 요구사항에 맞는 노드만 사용
+        let mut used: HashSet<String> = HashSet::new();
+        let mut used_domains: HashSet<String> = HashSet::new();
+        for i in 0..fanout {
+            let container = targets
+                .get(i)
+                .map(String::as_str)
+                .unwrap_or(DEFAULT_SHARD_CONTAINER);
+            let info = self.get_container(container);
+            let pool = match &info {
+                Some(info) => self.find_candidates_for_container(info),
+                None => candidates.clone(),
+            };
+            // 한 번의 장애로 모든 샤드가 죽지 않도록 아직 안 쓴 failure_domain을 우선
+            let domain_of = |id: &String| self.nodes.get(id).and_then(|n| n.failure_domain.clone());
+            let fresh = pool.iter().find(|id| {
+                !used.contains(*id) && domain_of(id).is_none_or(|d| !used_domains.contains(&d))
+            });
+            let Some(node_id) = fresh
+                .or_else(|| pool.iter().find(|id| !used.contains(*id)))
+                .cloned()
+            else {
+                if live {
+                    warn!("[Shard] No node can host {} right now", container);
+                }
+                plan.unplaced.push(container.to_string());
+                continue;
+            };
+            used_domains.extend(domain_of(&node_id));
+
+            let profile = info.map(|c| c.resource_profile).unwrap_or_default();
+            let effective_opi = self
+                .nodes
+                .get(&node_id)
+                .map(|n| self.profiled_effective_opi(&n, profile))
+                .unwrap_or(0.0);
+            used.insert(node_id.clone());
+            plan.shards.push(PlannedShard {
+                container_id: container.to_string(),
+                node_id,
+                effective_opi,
+            });
+        }
+
+        plan
+    }
+
+    // "왜 샤딩이 안 되지?" 진단용. 상태를 바꾸지 않는다
+    pub fn sharding_status(&self) -> ShardingStatus {
+        let (cpu_load, gpu_load, vram_usage_ratio, thresholds, trigger) = {
+            let status = self.server_status.lock();
+            let thresholds = self.thresholds_for(&status);
+            (
+                status.cpu_load,
+                status.gpu_load,
+                status.vram_usage_ratio,
+                thresholds,
+                Self::overload_cause(&status, &thresholds),
+            )
+        };
+        let candidates = self.find_smart_candidates();
+        let eligible_candidates = candidates.len();
+
+        let blocked = if trigger.is_none() {
+            Some(ShardingBlock::BelowThreshold)
+        } else if eligible_candidates > 0 {
+            let aggregate_opi = self.aggregate_candidate_opi(&candidates);
+            (!self.admits_sharding(eligible_candidates, aggregate_opi))
+                .then_some(ShardingBlock::InsufficientCapacity)
+        } else if self.nodes.iter().all(|e| e.value().is_quarantined) && !self.nodes.is_empty() {
+            Some(ShardingBlock::CapacityEmergency)
+        } else {
+            Some(ShardingBlock::NoCandidates)
+        };
+
+        ShardingStatus {
+            cpu_load,
+            gpu_load,
+            vram_usage_ratio,
+            thresholds,
+            trigger,
+            eligible_candidates,
+            blocked,
+        }
+    }
+
+    // 모든 노드가 격리된 상태에서 과부하가 오면 마스터 혼자 버티게 되므로 경보를 올리고,
+    // 설정 시 가장 최근까지 살아있던 격리 노드 하나를 최후 수단으로 복귀시킨다
+    fn handle_capacity_emergency(&self) -> Option<String> {
+        let total = self.nodes.len();
+        let quarantined = self
+            .nodes
+            .iter()
+            .filter(|e| e.value().is_quarantined)
+            .count();
+        if total == 0 || quarantined < total {
+            return None;
+        }
+
+        self.capacity_emergency.store(true, AtomicOrdering::Relaxed);
+        error!(
+            "[CRITICAL] All {} node(s) quarantined while master is overloaded; no offload capacity",
+            total
+        );
+
+        if !self.config.emergency_revive_last_resort {
+            return None;
+        }
+
+        let revive_id = self
+            .nodes
+            .iter()
+            .filter(|e| {
+                let n = e.value();
+                n.is_quarantined
+                    && !n.capability_invalid
+                    && !self.is_held_for_regression(&n.node_id)
+            })
+            .max_by(|a, b| {
+                a.value()
+                    .last_seen
+                    .cmp(&b.value().last_seen)
+                    .then_with(|| b.value().failure_count.cmp(&a.value().failure_count))
+            })
+            .map(|e| e.key().clone())?;
+
+        let now = self.clock.now();
+        let grace = Duration::from_secs_f64(self.config.emergency_revive_grace_secs.max(0.0));
+        let mut node = self.nodes.get_mut(&revive_id)?;
+        if let Some(mut stats) = self.node_stats.get_mut(&revive_id) {
+            stats.admin_quarantined = false;
+            stats.revived_until = Some(now + grace);
+        }
+        // 응답이 끊겨 Offline으로 내려간 노드도 유예 동안은 하드웨어 Tier로 후보에 오른다
+        let before = node.health_state;
+        node.failure_count = 0;
+        node.is_quarantined = false;
+        node.health_state = HealthState::Degraded;
+        self.refresh_tier(&mut node);
+        self.note_health_change(&revive_id, before, node.health_state);
+        warn!(
+            "[CRITICAL] Last-resort revive of quarantined node {} (tier {:?}, grace {:.0}s)",
+            revive_id,
+            node.current_tier,
+            grace.as_secs_f64()
+        );
+        Some(revive_id)
+    }
+
+    pub fn is_capacity_emergency(&self) -> bool {
+        self.capacity_emergency.load(AtomicOrdering::Relaxed)
+    }
+
+    // 부하 도메인이 알려져 있고 도메인별 임계값이 있으면 그것을, 아니면 전역 임계값을 사용
+    fn thresholds_for(&self, status: &ServerStatus) -> OverloadThresholds {
+        status
+            .load_domain
+            .as_ref()
+            .and_then(|d| self.config.domain_thresholds.get(d))
+            .copied()
+            .unwrap_or(self.thresholds)
+    }
+
+    // 두 임계치를 모두 넘었으면 상대적으로 더 많이 넘은 쪽이 원인
+    fn overload_cause(status: &ServerStatus, t: &OverloadThresholds) -> Option<OverloadCause> {
+        let cpu_ratio = status.cpu_load / t.cpu_max;
+        let vram_ratio = status.vram_usage_ratio / t.vram_pressure_max;
+
+        match (
+            status.cpu_load > t.cpu_max,
+            status.vram_usage_ratio > t.vram_pressure_max,
+        ) {
+            (true, true) if vram_ratio >= cpu_ratio => Some(OverloadCause::Vram),
+            (true, _) => Some(OverloadCause::Cpu),
+            (false, true) => Some(OverloadCause::Vram),
+            (false, false) => None,
+        }
+    }
+
+    // overload_cause는 우세한 원인 하나만, 이쪽은 넘은 임계치를 모두 반환 (로그용)
+    fn tripped_thresholds(status: &ServerStatus, t: &OverloadThresholds) -> Vec<OverloadCause> {
+        let mut tripped = Vec::new();
+        if status.cpu_load > t.cpu_max {
+            tripped.push(OverloadCause::Cpu);
+        }
+        if status.vram_usage_ratio > t.vram_pressure_max {
+            tripped.push(OverloadCause::Vram);
+        }
+        tripped
+    }
+
+    // 병목 자원을 가장 많이 쓰는 워크로드부터 내보낸다
+    fn shed_targets(workloads: &[MasterWorkload], cause: OverloadCause) -> Vec<String> {
+        let mut sorted: Vec<&MasterWorkload> = workloads.iter().collect();
+        match cause {
+            OverloadCause::Vram => sorted.sort_by(|a, b| b.vram_gb.total_cmp(&a.vram_gb)),
+            OverloadCause::Cpu => sorted.sort_by(|a, b| b.cpu_share.total_cmp(&a.cpu_share)),
+        }
+        sorted.into_iter().map(|w| w.container_id.clone()).collect()
+    }
+
+    fn find_smart_candidates(&self) -> Vec<String> {
+        self.find_smart_candidates_evaluated().0
+    }
+
+    fn find_smart_candidates_evaluated(&self) -> (Vec<String>, Evaluations) {
+        let (ranked, evaluated) =
+            self.rank_candidates_evaluated(ResourceProfile::Combined, |_| Some(1.0));
+        (self.spread_failure_domains(ranked), evaluated)
+    }
+
+    // 순위는 유지하되 같은 failure_domain의 두 번째 노드는 다른 도메인 노드들 뒤로 미룬다
+    fn spread_failure_domains(&self, ranked: Vec<String>) -> Vec<String> {
+        let want = ranked.len();
+        self.pick_spread(ranked, Vec::new(), want, |n| n.failure_domain.clone())
+    }
+
+    fn find_candidates_for_container(&self, container: &ContainerInfo) -> Vec<String> {
+        self.find_candidates_for_container_evaluated(container).0
+    }
+
+    fn find_candidates_for_container_evaluated(
+        &self,
+        container: &ContainerInfo,
+    ) -> (Vec<String>, Evaluations) {
+        let peer_zones = self.peer_zones(container);
+        let locality = self.config.peer_zone_multiplier.max(0.0);
+
+        let (ranked, evaluated) =
+            self.rank_candidates_evaluated(container.resource_profile, |node| {
+                (Self::container_fits(node, container) && self.meets_sla(node, container.sla)).then(
+                    || {
+                        let near_peers = node.zone.as_ref().is_some_and(|z| peer_zones.contains(z));
+                        let locality = if near_peers { locality } else { 1.0 };
+                        self.accelerator_affinity(node, &container.domain) * locality
+                    },
+                )
+            });
+        (self.spread_failure_domains(ranked), evaluated)
+    }
+
+    // communicates_with 컨테이너들이 현재 떠 있는 zone (replica 멤버 + 진행 중 Shard)
+    fn peer_zones(&self, container: &ContainerInfo) -> HashSet<String> {
+        if container.communicates_with.is_empty() {
+            return HashSet::new();
+        }
+
+        let mut hosts: HashSet<String> = HashSet::new();
+        for peer in &container.communicates_with {
+            if let Some(members) = self.replica_sets.get(peer) {
+                hosts.extend(members.iter().cloned());
+            }
+        }
+        hosts.extend(
+            self.assignments
+                .read()
+                .values()
+                .filter(|a| {
+                    a.status != AssignmentStatus::Rejected
+                        && container.communicates_with.contains(&a.container)
+                })
+                .map(|a| a.node_id.clone()),
+        );
+
+        hosts
+            .iter()
+            .filter_map(|id| self.nodes.get(id).and_then(|n| n.zone.clone()))
+            .collect()
+    }
+
+    // 도메인 x 가속기 친화도: 노드가 가진 가속기에 해당하는 항목의 배수를 모두 곱한다
+    fn accelerator_affinity(&self, node: &NodeContext, domain: &str) -> f64 {
+        self.config
+            .accelerator_affinity
+            .iter()
+            .filter(|a| {
+                a.domain.eq_ignore_ascii_case(domain) && a.accelerator.is_supported_by(node)
+            })
+            .map(|a| a.multiplier.max(0.0))
+            .product()
+    }
+
+    // 상태 기반 필터 (하드웨어 적합성과 별개)
+    fn meets_sla(&self, node: &NodeContext, sla: SlaClass) -> bool {
+        match sla {
+            SlaClass::BestEffort | SlaClass::Standard => true,
+            SlaClass::Premium => {
+                node.health_state == HealthState::Healthy
+                    && node.net_rtt_ema_ms <= self.config.premium_max_rtt_ms
+            }
+        }
+    }
+
+    fn required_replicas(&self, container: &ContainerInfo) -> u32 {
+        match container.sla {
+            SlaClass::BestEffort => 1,
+            SlaClass::Standard => container.min_replicas.max(1),
+            SlaClass::Premium => container
+                .min_replicas
+                .max(self.config.premium_min_replicas)
+                .max(1),
+        }
+    }
+
+    // 컨테이너가 해당 노드에서 실행 가능한지 (하드웨어 요구사항)
+    fn container_fits(node: &NodeContext, container: &ContainerInfo) -> bool {
+        if node.is_cpu_only() && container.is_gpu_bound() {
+            return false;
+        }
+        // OPI가 아무리 높아도 모델을 못 띄우는 노드는 제외
+        if !node.can_serve(container) {
+            return false;
+        }
+        // 모델이 VRAM에 안 들어가는 노드에 보내면 바로 OOM
+        if node.total_vram_gb < container.required_vram_gb {
+            return false;
+        }
+        container.required_accelerator.is_supported_by(node)
+    }
+
+    // accept: None = 후보 제외, Some(m) = effective OPI에 곱할 배수
+    fn rank_candidates<F>(&self, profile: ResourceProfile, accept: F) -> Vec<String>
+    where
+        F: Fn(&NodeContext) -> Option<f64>,
+    {
+        self.rank_candidates_evaluated(profile, accept).0
+    }
+
+    // 순위와 함께 이번에 실제로 평가한 노드별 결과를 돌려준다 (fast path면 표본만)
+    fn rank_candidates_evaluated<F>(
+        &self,
+        profile: ResourceProfile,
+        accept: F,
+    ) -> (Vec<String>, Evaluations)
+    where
+        F: Fn(&NodeContext) -> Option<f64>,
+    {
+        // (node_id, effective OPI, 시간당 비용, RTT EMA)
+        let mut candidates: Vec<(String, f64, Option<f64>, f64)> = Vec::new();
+        let record = self.config.decision_history_capacity > 0;
+        let mut evaluated = Evaluations::new();
+        // 컨테이너 요구사항 불일치(accept = None)는 이력상 Considered로 남는다. 후보가 되면 true
+        let mut consider = |node: &NodeContext| match self.evaluate_candidate(node, profile) {
+            Ok((id, effective_opi, cost)) => {
+                if record {
+                    evaluated.push((id.clone(), HistoryEvent::Considered { effective_opi }));
+                }
+                let Some(m) = accept(node) else {
+                    return false;
+                };
+                candidates.push((id, effective_opi * m, cost, node.net_rtt_ema_ms));
+                true
+            }
+            Err(reason) => {
+                if record {
+                    evaluated.push((node.node_id.clone(), HistoryEvent::Rejected(reason)));
+                }
+                false
+            }
+        };
+
+        if self.nodes.len() > self.config.fast_path_fleet_threshold {
+            // 대규모 fleet: nodes를 순회하지 않고 인덱스에서 무작위 k개만 꺼내 평가 (power-of-k)
+            let k = self.config.fast_path_sample_size.max(1);
+            let budget = std::time::Duration::from_secs_f64(
+                self.config.fast_path_budget_ms.max(0.0) / 1000.0,
+            );
+            // 실제 소요 시간 예산이므로 주입된 시계가 아니라 실제 시계로 잰다
+            let started = Instant::now();
+            let sampled = self.sample_node_ids(k);
+            let mut found = false;
+            for id in &sampled {
+                if started.elapsed() > budget {
+                    break;
+                }
+                if let Some(node) = self.nodes.get(id) {
+                    found |= consider(node.value());
+                }
+            }
+
+            // 소수 노드만 처리할 수 있는 컨테이너는 표본에 없을 수 있다.
+            // 표본에서 후보가 하나도 안 나오면 전체를 훑어서 일시적 NoCapacity 오판을 막는다
+            if !found {
+                debug!(
+                    "[Scheduler] Fast path sample of {} had no candidate; scanning all {} nodes",
+                    sampled.len(),
+                    self.nodes.len()
+                );
+                let sampled: HashSet<String> = sampled.into_iter().collect();
+                for entry in self.nodes.iter() {
+                    if !sampled.contains(entry.key()) {
+                        consider(entry.value());
+                    }
+                }
+            }
+        } else {
+            for entry in self.nodes.iter() {
+                consider(entry.value());
+            }
+        }
+
+        // NaN 점수가 섞여도 패닉 없이 맨 뒤로 보낸다
+        candidates.sort_by(|a, b| {
+            b.1.partial_cmp(&a.1)
+                .unwrap_or_else(|| a.1.is_nan().cmp(&b.1.is_nan()))
+                .then_with(|| Self::tie_break((&a.0, a.3), (&b.0, b.3)))
+        });
+
+        if let RankingMode::CheapestAboveFloor { min_effective_opi } = self.config.ranking_mode {
+            // 성능 하한을 넘고 비용이 있는 노드를 싼 순서로 앞에, 나머지는 성능 순서 유지
+            let (mut priced, rest): (Vec<_>, Vec<_>) = candidates
+                .into_iter()
+                .partition(|c| c.1 >= min_effective_opi && c.2.is_some());
+            priced.sort_by(|a, b| a.2.unwrap_or(0.0).total_cmp(&b.2.unwrap_or(0.0)));
+            candidates = priced.into_iter().chain(rest).collect();
+        }
+
+        let ranked = candidates.into_iter().map(|(id, _, _, _)| id).collect();
+        (ranked, evaluated)
+    }
+
+    // node_index에서 중복 없이 최대 k개를 고른다. 인덱스는 복사하지 않고
+    // 바꾼 위치만 기록하는 부분 Fisher-Yates라 비용은 fleet 크기와 무관하게 O(k)
+    fn sample_node_ids(&self, k: usize) -> Vec<String> {
+        let index = self.node_index.read();
+        let n = index.len();
+        let k = k.min(n);
+        let mut rng = uuid::Uuid::new_v4().as_u128() as u64 | 1;
+        let mut swapped: HashMap<usize, usize> = HashMap::with_capacity(k);
+        let mut picked = Vec::with_capacity(k);
+        for i in 0..k {
+            // xorshift64
+            rng ^= rng << 13;
+            rng ^= rng >> 7;
+            rng ^= rng << 17;
+            let j = i + (rng % (n - i) as u64) as usize;
+            let at_j = swapped.get(&j).copied().unwrap_or(j);
+            let at_i = swapped.get(&i).copied().unwrap_or(i);
+            swapped.insert(j, at_i);
+            picked.push(index[at_j].clone());
+        }
+        picked
+    }
+
+    // 점수가 같으면 RTT가 낮은 노드(샘플 없음은 뒤), 그다음 node_id 사전순.
+    // DashMap 순회 순서와 무관하게 같은 상태면 항상 같은 순위가 나오도록
+    fn tie_break(a: (&str, f64), b: (&str, f64)) -> std::cmp::Ordering {
+        let rtt_key = |rtt: f64| if rtt > 0.0 { rtt } else { f64::INFINITY };
+        rtt_key(a.1)
+            .total_cmp(&rtt_key(b.1))
+            .then_with(|| a.0.cmp(b.0))
+    }
+
+    fn score_candidate(
+        &self,
+        node: &NodeContext,
+        profile: ResourceProfile,
+    ) -> Option<(String, f64, Option<f64>)> {
+        self.evaluate_candidate(node, profile).ok()
+    }
+
+    // 후보에서 빠진 이유까지 돌려준다 (결정 이력 기록용)
+    fn evaluate_candidate(
+        &self,
+        node: &NodeContext,
+        profile: ResourceProfile,
+    ) -> Result<(String, f64, Option<f64>), RejectReason> {
+        if !node.user_allowed {
+            return Err(RejectReason::NotAllowed);
+        }
+        if node.capability_invalid {
+            return Err(RejectReason::CapabilityInvalid);
+        }
+        if node.current_tier == NodeTier::Offline {
+            return Err(RejectReason::Offline);
+        }
+
+        // 드레인 중인 노드는 진행 중 작업만 마무리 (헬스 판정과 별개)
+        if node.is_draining {
+            return Err(RejectReason::Draining);
+        }
+
+        // 과부하 노드 제외
+        if self.is_overloaded(node, profile) {
+            return Err(RejectReason::Overloaded);
+        }
+
+        // 헬스/격리 상태 반영
+        if node.is_quarantined || node.health_state == HealthState::Quarantined {
+            return Err(RejectReason::Quarantined);
+        }
+
+        // 재시작 직후 Heartbeat가 아직 없는 노드는 계산값 대신 복원된 점수로 임시 순위 (자격 검사는 동일)
+        let eff_opi = match self.warm_start_scores.get(&node.node_id) {
+            Some(score) => *score,
+            None => self.profiled_effective_opi(node, profile),
+        };
+        if !Self::is_schedulable_opi(eff_opi) {
+            return Err(RejectReason::LowOpi);
+        }
+
+        Ok((node.node_id.clone(), eff_opi, node.cost_per_hour))
+    }
+
+    // 실제 배치 결정 시에만 호출 (진단/드라이런 경로는 기록하지 않음).
+    // 후보 선정 때 나온 평가 결과를 그대로 기록하고, 선택된 노드는 선택 이벤트로 대체
+    fn record_decision_pass(&self, evaluated: Evaluations, chosen: &HashMap<String, HistoryEvent>) {
+        if self.config.decision_history_capacity == 0 {
+            return;
+        }
+        let now = self.clock.now();
+        for (id, event) in evaluated {
+            if !chosen.contains_key(&id) {
+                self.push_history(&id, now, event);
+            }
+        }
+        for (id, event) in chosen {
+            self.push_history(id, now, event.clone());
+        }
+    }
+
+    fn push_history(&self, id: &str, at: Instant, event: HistoryEvent) {
+        let Some(mut stats) = self.node_stats.get_mut(id) else {
+            return;
+        };
+        while stats.decision_history.len() >= self.config.decision_history_capacity {
+            stats.decision_history.pop_front();
+        }
+        stats.decision_history.push_back(HistoryEntry { at, event });
+    }
+
+    // 하나의 Task를 shard_count개로 나눠 상위 후보들에 돌아가며 배정
+    pub fn shard_task(&self, container: &str, shard_count: u32) -> Vec<(String, ServerCommand)> {
+        let candidates = match self.get_container(container) {
+            Some(info) => self.find_candidates_for_container(&info),
+            None => self.find_smart_candidates(),
+        };
+        if candidates.is_empty() || shard_count == 0 {
+            return Vec::new();
+        }
+
+        let task_id = uuid::Uuid::new_v4().to_string();
+        let mut commands = Vec::with_capacity(shard_count as usize);
+        for shard_index in 0..shard_count {
+            let node_id = &candidates[shard_index as usize % candidates.len()];
+            let cmd = self.build_shard_command(&task_id, shard_index, shard_count, container);
+            self.track_assignment(node_id, &cmd);
+            commands.push((node_id.clone(), cmd));
+        }
+
+        self.note_task_sharded(
+            &task_id,
+            commands.iter().map(|(id, _)| id.clone()).collect(),
+        );
+        commands
+    }
+
+    // Shard를 실제로 발급한 모든 경로에서 호출 (구독자가 일부 Shard를 놓치지 않도록)
+    fn note_task_sharded(&self, task_id: &str, node_ids: Vec<String>) {
+        if node_ids.is_empty() {
+            return;
+        }
+        info!(
+            "[Task] {} sharded into {} on {:?}",
+            task_id,
+            node_ids.len(),
+            node_ids
+        );
+        self.emit_event(SchedulerEvent::TaskSharded {
+            task_id: task_id.to_string(),
+            shard_count: node_ids.len() as u32,
+            node_ids,
+        });
+    }
+
+    fn create_shard_command(&self, next_container: &str) -> ServerCommand {
+        let task_id = uuid::Uuid::new_v4().to_string();
+        self.build_shard_command(&task_id, 0, 1, next_container)
+    }
+
+    // data를 후보 수만큼 거의 같은 크기로 나눠 (node_id, 명령) 쌍으로 만든다.
+    // 데이터가 후보 수보다 짧으면 빈 Shard를 만들지 않도록 Shard 수를 줄인다.
+    // shard_task와 마찬가지로 각 Shard를 추적하므로 Ack/진행률/반납을 그대로 받을 수 있다
+    pub fn create_shard_commands(
+        &self,
+        task_id: &str,
+        container: &str,
+        data: &[u8],
+        candidates: &[String],
+    ) -> Vec<(String, ServerCommand)> {
+        if data.is_empty() || candidates.is_empty() {
+            return Vec::new();
+        }
+
+        let total = candidates.len().min(data.len());
+        let base = data.len() / total;
+        let extra = data.len() % total;
+        let mut offset = 0;
+
+        let commands: Vec<(String, ServerCommand)> = candidates
+            .iter()
+            .take(total)
+            .enumerate()
+            .map(|(i, node_id)| {
+                // 앞쪽 extra개 Shard가 1바이트씩 더 가진다
+                let len = base + usize::from(i < extra);
+                let chunk = &data[offset..offset + len];
+                offset += len;
+
+                let mut cmd = self.build_shard_command(task_id, i as u32, total as u32, container);
+                if let Some(ServerPayload::Shard(shard)) = cmd.payload.as_mut() {
+                    shard.data = chunk.to_vec();
+                }
+                self.track_assignment(node_id, &cmd);
+                (node_id.clone(), cmd)
+            })
+            .collect();
+
+        self.note_task_sharded(task_id, commands.iter().map(|(id, _)| id.clone()).collect());
+        commands
+    }
+
+    // shard_id는 발행마다 새로 만들고, 추적은 (task_id, shard_index) 기준
+    fn build_shard_command(
+        &self,
+        task_id: &str,
+        shard_index: u32,
+        shard_total: u32,
+        next_container: &str,
+    ) -> ServerCommand {
+        use stc::ShardPayload;
+
+        ServerCommand {
+            r#type: ServerCmdType::ShardTask as i32,
+            task_id: task_id.to_string(),
+            payload: Some(ServerPayload::Shard(ShardPayload {
+                shard_id: uuid::Uuid::new_v4().to_string(),
+                shard_index,
+                shard_total,
+                data: Vec::new(),
+                next_container: next_container.into(),
+                buffer_tag: "default".into(),
+            })),
+        }
+    }
+
+    // ---------------- Dispatch -> Ack -> Active ----------------
+
+    fn track_assignment(&self, node_id: &str, cmd: &ServerCommand) {
+        let Some(ServerPayload::Shard(shard)) = &cmd.payload else {
+            return;
+        };
+        metrics::shard_command_issued();
+
+        let now = self.clock.now();
+        let (status, last_progress_at) = if self.config.require_shard_ack {
+            (AssignmentStatus::Pending, None)
+        } else {
+            (AssignmentStatus::Active, Some(now))
+        };
+
+        self.assignments.write().insert(
+            (cmd.task_id.clone(), shard.shard_index),
+            Assignment {
+                shard_id: shard.shard_id.clone(),
+                task_id: cmd.task_id.clone(),
+                shard_index: shard.shard_index,
+                shard_total: shard.shard_total,
+                container: shard.next_container.clone(),
+                node_id: node_id.to_string(),
+                status,
+                created_at: now,
+                reject_reason: None,
+                progress: 0.0,
+                last_progress_at,
+            },
+        );
+        // 부하가 Heartbeat에 잡히기 전까지는 Offload와 같이 예약으로 센다
+        if let Some(mut node) = self.nodes.get_mut(node_id) {
+            node.pending_reservations = node.pending_reservations.saturating_add(1);
+            node.reserved_at = Some(now);
+        }
+    }
+
+    // 재배치로 같은 슬롯에 새 shard_id가 발급됐다면 이전 shard_id의 보고는 무시
+    fn lookup_assignment_mut(
+        &self,
+        task_id: &str,
+        shard_index: u32,
+        shard_id: &str,
+        node_id: &str,
+    ) -> Result<MappedRwLockWriteGuard<'_, Assignment>, AssignmentError> {
+        let key = (task_id.to_string(), shard_index);
+        let assignment = RwLockWriteGuard::try_map(self.assignments.write(), |m| {
+            m.get_mut(&key).filter(|a| a.shard_id == shard_id)
+        })
+        .map_err(|_| AssignmentError::UnknownShard(shard_id.to_string()))?;
+
+        if assignment.node_id != node_id {
+            return Err(AssignmentError::NodeMismatch {
+                shard_id: shard_id.to_string(),
+                expected: assignment.node_id.clone(),
+                got: node_id.to_string(),
+            });
+        }
+        Ok(assignment)
+    }
+
+    pub fn handle_ack(&self, ack: &AckPayload) -> Result<AssignmentStatus, AssignmentError> {
+        let mut assignment =
+            self.lookup_assignment_mut(&ack.task_id, ack.shard_index, &ack.shard_id, &ack.node_id)?;
+
+        if assignment.status != AssignmentStatus::Pending {
+            return Err(AssignmentError::AlreadyResolved(ack.shard_id.clone()));
+        }
+
+        if ack.accepted {
+            assignment.status = AssignmentStatus::Active;
+            assignment.last_progress_at = Some(self.clock.now());
+            info!("[Ack] {} accepted shard {}", ack.node_id, ack.shard_id);
+        } else {
+            assignment.status = AssignmentStatus::Rejected;
+            assignment.reject_reason = Some(ack.reason.clone());
+            warn!(
+                "[Ack] {} rejected shard {}: {}",
+                ack.node_id, ack.shard_id, ack.reason
+            );
+        }
+
+        let status = assignment.status;
+        drop(assignment);
+        if status == AssignmentStatus::Rejected {
+            self.release_reservation(&ack.node_id);
+        }
+        Ok(status)
+    }
+
+    pub fn handle_progress(&self, progress: &ProgressPayload) -> Result<(), AssignmentError> {
+        let mut assignment = self.lookup_assignment_mut(
+            &progress.task_id,
+            progress.shard_index,
+            &progress.shard_id,
+            &progress.node_id,
+        )?;
+
+        if assignment.status != AssignmentStatus::Active {
+            return Err(AssignmentError::NotActive(progress.shard_id.clone()));
+        }
+
+        assignment.progress = if progress.progress.is_finite() {
+            progress.progress.clamp(0.0, 1.0)
+        } else {
+            assignment.progress
+        };
+        assignment.last_progress_at = Some(self.clock.now());
+        Ok(())
+    }
+
+    // 타임아웃과 달리 명시적인 반납: 사유를 남기고, 노드를 잠시 후순위로 돌린 뒤 다른 곳에 재배치
+    pub fn handle_shard_rejection(
+        &self,
+        reject: &ShardRejectPayload,
+    ) -> Result<Vec<(String, ServerCommand)>, AssignmentError> {
+        let rejected = {
+            let mut assignment = self.lookup_assignment_mut(
+                &reject.task_id,
+                reject.shard_index,
+                &reject.shard_id,
+                &reject.node_id,
+            )?;
+            if assignment.status == AssignmentStatus::Rejected {
+                return Err(AssignmentError::AlreadyResolved(reject.shard_id.clone()));
+            }
+
+            assignment.status = AssignmentStatus::Rejected;
+            assignment.reject_reason = Some(reject.reason.clone());
+            assignment.clone()
+        };
+
+        warn!(
+            "[Reject] {} declined shard {} of task {}: {}",
+            reject.node_id, reject.shard_id, rejected.task_id, reject.reason
+        );
+
+        self.release_reservation(&reject.node_id);
+        let until = self.clock.now()
+            + std::time::Duration::from_secs_f64(self.config.rejection_penalty_secs.max(0.0));
+        self.rejection_penalties
+            .insert(reject.node_id.clone(), until);
+
+        Ok(self.replace_assignments(&[rejected]))
+    }
+
+    // Heartbeat는 오지만 작업은 진행되지 않는 Shard (liveness와 별개의 신호)
+    pub fn stalled_assignments(&self) -> Vec<Assignment> {
+        let now = self.clock.now();
+        let stall = self.config.progress_stall_secs;
+
+        self.assignments
+            .read()
+            .values()
+            .filter(|a| {
+                a.status == AssignmentStatus::Active
+                    && a.last_progress_at
+                        .is_some_and(|t| now.saturating_duration_since(t).as_secs_f64() > stall)
+            })
+            .cloned()
+            .collect()
+    }
+
+    // 노드 제거 등으로 갈 곳을 잃은 할당을 다른 노드에 다시 배치 (task_id 유지, 새 shard_id)
+    pub fn replace_assignments(&self, orphaned: &[Assignment]) -> Vec<(String, ServerCommand)> {
+        let mut commands = Vec::new();
+
+        for old in orphaned {
+            let candidate = match self.get_container(&old.container) {
+                Some(container) => self.find_candidates_for_container(&container),
+                None => self.find_smart_candidates(),
+            }
+            .into_iter()
+            .find(|id| *id != old.node_id);
+
+            let Some(node_id) = candidate else {
+                warn!(
+                    "[Assignment] No node to re-place shard {} of task {}",
+                    old.shard_id, old.task_id
+                );
+                continue;
+            };
+
+            let cmd = self.build_shard_command(
+                &old.task_id,
+                old.shard_index,
+                old.shard_total,
+                &old.container,
+            );
+            self.track_assignment(&node_id, &cmd);
+            info!(
+                "[Assignment] Task {} re-placed: {} -> {}",
+                old.task_id, old.node_id, node_id
+            );
+            commands.push((node_id, cmd));
+        }
+
+        // 같은 Task의 재배치 Shard는 이벤트 하나로 묶는다
+        let mut by_task: Vec<(&str, Vec<String>)> = Vec::new();
+        for (node_id, cmd) in &commands {
+            match by_task.iter_mut().find(|(t, _)| *t == cmd.task_id) {
+                Some((_, node_ids)) => node_ids.push(node_id.clone()),
+                None => by_task.push((&cmd.task_id, vec![node_id.clone()])),
+            }
+        }
+        for (task_id, node_ids) in by_task {
+            self.note_task_sharded(task_id, node_ids);
+        }
+
+        commands
+    }
+
+    // 평범한 노드에 올라간 Shard 중, 훨씬 나은 유휴 노드가 생긴 것을 찾아 이동을 제안.
+    // 점수가 낮은 Shard부터 보고, 한 노드에는 한 Shard만 제안한다
+    pub fn rebalance_shards(&self, active: &[Assignment]) -> Vec<Rebalance> {
+        let threshold = 1.0 + self.config.rebalance_min_improvement.max(0.0);
+        let mut busy: HashSet<String> = active.iter().map(|a| a.node_id.clone()).collect();
+        busy.extend(
+            self.assignments
+                .read()
+                .values()
+                .filter(|a| a.status != AssignmentStatus::Rejected)
+                .map(|a| a.node_id.clone()),
+        );
+
+        let mut scored: Vec<(&Assignment, f64)> = active
+            .iter()
+            .filter(|a| a.status != AssignmentStatus::Rejected)
+            .map(|a| (a, self.effective_opi(&a.node_id).unwrap_or(0.0)))
+            .collect();
+        scored.sort_by(|a, b| a.1.total_cmp(&b.1));
+
+        let mut moves = Vec::new();
+        for (shard, from_opi) in scored {
+            let candidates = match self.get_container(&shard.container) {
+                Some(container) => self.find_candidates_for_container(&container),
+                None => self.find_smart_candidates(),
+            };
+            // 후보는 순위순이므로 첫 유휴 노드가 최선
+            let Some((to_node, to_opi)) = candidates
+                .into_iter()
+                .filter(|id| !busy.contains(id))
+                .find_map(|id| self.effective_opi(&id).map(|opi| (id, opi)))
+            else {
+                continue;
+            };
+            if to_opi <= from_opi * threshold {
+                continue;
+            }
+
+            busy.insert(to_node.clone());
+            moves.push(Rebalance {
+                shard_id: shard.shard_id.clone(),
+                task_id: shard.task_id.clone(),
+                shard_index: shard.shard_index,
+                from_node: shard.node_id.clone(),
+                to_node,
+                from_opi,
+                to_opi,
+            });
+        }
+
+        if !moves.is_empty() {
+            info!("[Rebalance] {} shard move(s) proposed", moves.len());
+        }
+        moves
+    }
+
+    pub fn assignment(&self, task_id: &str, shard_index: u32) -> Option<Assignment> {
+        self.assignments
+            .read()
+            .get(&(task_id.to_string(), shard_index))
+            .cloned()
+    }
+
+    pub fn task_status(&self, task_id: &str) -> Option<TaskStatus> {
+        let assignments = self.assignments.read();
+        let shards: Vec<&Assignment> = assignments
+            .values()
+            .filter(|a| a.task_id == task_id)
+            .collect();
+        let shard_total = shards.iter().map(|a| a.shard_total).max()?.max(1);
+
+        let count = |status| shards.iter().filter(|a| a.status == status).count() as u32;
+        let completed = shards
+            .iter()
+            .filter(|a| a.status == AssignmentStatus::Active && a.progress >= 1.0)
+            .count() as u32;
+        let progress = shards
+            .iter()
+            .filter(|a| a.status != AssignmentStatus::Rejected)
+            .map(|a| a.progress)
+            .sum::<f64>()
+            / shard_total as f64;
+
+        Some(TaskStatus {
+            task_id: task_id.to_string(),
+            shard_total,
+            tracked_shards: shards.len() as u32,
+            pending: count(AssignmentStatus::Pending),
+            active: count(AssignmentStatus::Active),
+            rejected: count(AssignmentStatus::Rejected),
+            completed,
+            progress: progress.min(1.0),
+            is_complete: completed >= shard_total,
+        })
+    }
+
+    fn emit_event(&self, event: SchedulerEvent) {
+        if let Some(tx) = self.subscribers.sender.lock().as_ref() {
+            let _ = tx.send(event.clone());
+        }
+        let mut events = self.events.lock();
+        if events.len() >= MAX_PENDING_EVENTS {
+            events.pop_front();
+        }
+        events.push_back(event);
+    }
+
+    // 여러 구독자 가능. 첫 구독 시 전달 스레드를 띄우고, 스케줄러가 drop되면 스레드도 끝난다
+    pub fn on_event(&self, callback: EventCallback) {
+        self.subscribers.callbacks.write().push(callback);
+
+        let mut sender = self.subscribers.sender.lock();
+        if sender.is_some() {
+            return;
+        }
+        let (tx, rx) = mpsc::channel::<SchedulerEvent>();
+        let callbacks = Arc::clone(&self.subscribers.callbacks);
+        let spawned = std::thread::Builder::new()
+            .name("stc-scheduler-events".into())
+            .spawn(move || {
+                for event in rx {
+                    for callback in callbacks.read().iter() {
+                        callback(event.clone());
+                    }
+                }
+            });
+        match spawned {
+            Ok(_) => *sender = Some(tx),
+            Err(e) => error!("[Events] Failed to start subscriber thread: {}", e),
+        }
+    }
+
+    fn note_tier_change(&self, node_id: &str, from: &NodeTier, to: &NodeTier) {
+        if from == to {
+            return;
+        }
+        metrics::tier_changed(from, to);
+        self.emit_event(SchedulerEvent::TierChanged {
+            node_id: node_id.to_string(),
+            from: from.clone(),
+            to: to.clone(),
+        });
+    }
+
+    fn note_health_change(&self, node_id: &str, from: HealthState, to: HealthState) {
+        if from == to {
+            return;
+        }
+        metrics::health_changed(from, to);
+        self.emit_event(SchedulerEvent::HealthChanged {
+            node_id: node_id.to_string(),
+            from,
+            to,
+        });
+    }
+
+    fn note_node_removed(&self, node: &NodeContext) {
+        self.node_index.write().retain(|id| *id != node.node_id);
+        metrics::node_removed(&node.current_tier, node.health_state);
+        self.emit_event(SchedulerEvent::NodeEvicted {
+            node_id: node.node_id.clone(),
+        });
+    }
+
+    pub fn drain_events(&self) -> Vec<SchedulerEvent> {
+        self.events.lock().drain(..).collect()
+    }
+
+    // 대시보드용 전체 할당 목록 (읽기 락 하나로 찍은 일관된 스냅샷)
+    pub fn all_assignments(&self) -> Vec<AssignmentRecord> {
+        let now = self.clock.now();
+        let mut records: Vec<AssignmentRecord> = self
+            .assignments
+            .read()
+            .values()
+            .map(|a| AssignmentRecord {
+                task_id: a.task_id.clone(),
+                shard_id: a.shard_id.clone(),
+                shard_index: a.shard_index,
+                container: a.container.clone(),
+                node_id: a.node_id.clone(),
+                status: a.status,
+                progress: a.progress,
+                age_secs: now.saturating_duration_since(a.created_at).as_secs_f64(),
+            })
+            .collect();
+        records.sort_by(|a, b| {
+            a.task_id
+                .cmp(&b.task_id)
+                .then(a.shard_index.cmp(&b.shard_index))
+        });
+        records
+    }
+
+    // ---------------- Time-to-Ready 배치 ----------------
+
+    // 모델이 캐시돼 있으면 0, 아니면 모델 크기 / 디스크 속도
+    fn estimate_startup_secs(&self, node: &NodeContext, container: &ContainerInfo) -> f64 {
+        let cached = node
+            .cached_models
+            .iter()
+            .any(|m| *m == container.id || container.ai_models.contains(m));
+        if cached || container.model_size_gb <= 0.0 {
+            return 0.0;
+        }
+
+        let speed = if node.storage_speed_mbps > 0.0 {
+            node.storage_speed_mbps
+        } else {
+            self.config.default_storage_speed_mbps
+        };
+        container.model_size_gb * 1024.0 / speed
+    }
+
+    // 지금 배치할 노드가 없을 때 일시적인 용량 부족인지, 영원히 불가능한지 구분해서 돌려준다
+    pub fn place_container(&self, container_id: &str) -> Result<String, PlacementError> {
+        let container = self
+            .get_container(container_id)
+            .ok_or_else(|| PlacementError::UnknownContainer(container_id.to_string()))?;
+
+        if let Some(node_id) = self
+            .find_candidates_for_container(&container)
+            .into_iter()
+            .next()
+        {
+            return Ok(node_id);
+        }
+
+        if let Some(reason) = self.infeasibility(&container) {
+            warn!("[Placement] {} is infeasible: {}", container_id, reason);
+            return Err(PlacementError::Infeasible {
+                container_id: container_id.to_string(),
+                reason,
+            });
+        }
+        Err(PlacementError::NoCapacity(container_id.to_string()))
+    }
+
+    // fleet 전체가 광고한 하드웨어 기준. 상태(부하/격리)는 일시적이므로 기본적으로 무시한다
+    fn infeasibility(&self, container: &ContainerInfo) -> Option<String> {
+        let include_offline = self.config.feasibility_includes_unavailable;
+        let eligible = |node: &NodeContext| {
+            include_offline || (!node.is_quarantined && node.current_tier != NodeTier::Offline)
+        };
+        let feasible = self
+            .nodes
+            .iter()
+            .any(|e| eligible(e.value()) && Self::container_fits(e.value(), container));
+        // 노드가 하나도 없으면 판단 근거가 없으므로 용량 부족으로 취급
+        if feasible || self.nodes.is_empty() {
+            return None;
+        }
+
+        // 하드웨어보다 태그가 먼저: 아무도 광고하지 않은 모델이면 GPU를 늘려도 소용없다
+        let advertised = self
+            .nodes
+            .iter()
+            .any(|e| eligible(e.value()) && e.value().can_serve(container));
+        Some(if !advertised {
+            let tags: Vec<&str> = std::iter::once(container.domain.as_str())
+                .chain(container.ai_models.iter().map(String::as_str))
+                .filter(|t| !t.is_empty())
+                .collect();
+            format!("no node advertises model/domain {}", tags.join(", "))
+        } else if container.required_accelerator != AcceleratorKind::Any {
+            format!("requires {:?} accelerator", container.required_accelerator)
+        } else if container.required_vram_gb > 0 {
+            format!("requires {} GB VRAM", container.required_vram_gb)
+        } else {
+            "requires a GPU node".to_string()
+        })
+    }
+
+    // 샤딩 경로 없이 "컨테이너 X를 지금 어디서 돌릴지"만 물을 때.
+    // 모르는 컨테이너와 가능한 노드 없음을 구분하려면 place_container 사용
+    pub fn best_node_for_container(&self, container_id: &str) -> Option<NodeSnapshot> {
+        let container = self.get_container(container_id)?;
+        let node_id = self
+            .find_candidates_for_container(&container)
+            .into_iter()
+            .next()?;
+        self.get_node(&node_id)
+    }
+
+    // 지연 민감 워크로드용: steady-state OPI 대신 준비 완료까지의 시간이 가장 짧은 노드
+    // (예상 시간이 같으면 OPI 순위를 따름)
+    pub fn place_for_time_to_ready(&self, container_id: &str) -> Option<(String, f64)> {
+        let container = self.get_container(container_id)?;
+
+        self.find_candidates_for_container(&container)
+            .into_iter()
+            .filter_map(|id| {
+                let node = self.nodes.get(&id)?;
+                let secs = self.estimate_startup_secs(&node, &container);
+                Some((id, secs))
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+    }
+
+    pub fn update_model_cache(&self, id: &str, cached_models: Vec<String>) -> bool {
+        match self.nodes.get_mut(id) {
+            Some(mut node) => {
+                node.cached_models = cached_models;
+                true
+            }
+            None => false,
+        }
+    }
+
+    // ---------------- Warm Start ----------------
+
+    // 복원된 스냅샷의 마지막 effective OPI를 등록된 노드에 한해 임시 점수로 사용
+    pub fn seed_warm_start<I>(&self, scores: I) -> usize
+    where
+        I: IntoIterator<Item = (String, f64)>,
+    {
+        let mut seeded = 0;
+        for (id, score) in scores {
+            if !self.nodes.contains_key(&id) || !score.is_finite() {
+                continue;
+            }
+            self.warm_start_scores.insert(id, score.max(0.0));
+            seeded += 1;
+        }
+        info!("[WarmStart] Seeded {} provisional score(s)", seeded);
+        seeded
+    }
+
+    pub fn clear_warm_start(&self) {
+        self.warm_start_scores.clear();
+    }
+
+    // ---------------- Replica 배치 ----------------
+
+    pub fn place_replicas(&self, container_id: &str) -> Option<ReplicaPlacement> {
+        let container = self.get_container(container_id)?;
+        let required = self.required_replicas(&container);
+        let node_ids = self.fill_replica_set(&container, required, Vec::new());
+
+        let placement = ReplicaPlacement {
+            container_id: container_id.to_string(),
+            required,
+            node_ids: node_ids.clone(),
+        };
+        if placement.is_complete() {
+            info!(
+                "[Replica] {} placed on {} node(s): {:?}",
+                container_id,
+                node_ids.len(),
+                node_ids
+            );
+        } else {
+            warn!(
+                "[Replica] {} partially placed: {}/{} node(s)",
+                container_id,
+                node_ids.len(),
+                required
+            );
+        }
+
+        self.replica_sets.insert(container_id.to_string(), node_ids);
+        Some(placement)
+    }
+
+    pub fn get_container(&self, id: &str) -> Option<ContainerInfo> {
+        self.container_registry.read().get(id).cloned()
+    }
+
+    // 같은 노드에 복제본을 두 번 올리지 않도록 기존 멤버를 제외하고 채운다
+    fn fill_replica_set(
+        &self,
+        container: &ContainerInfo,
+        required: u32,
+        members: Vec<String>,
+    ) -> Vec<String> {
+        let ranked = self.find_candidates_for_container(container);
+        self.pick_spread(ranked, members, required as usize, |n| n.zone.clone())
+    }
+
+    // 이미 쓰인 도메인과 다른 도메인을 먼저 고르고, 모자라면 순위대로 나머지를 채운다
+    fn pick_spread<F>(
+        &self,
+        ranked: Vec<String>,
+        mut members: Vec<String>,
+        want: usize,
+        domain_of: F,
+    ) -> Vec<String>
+    where
+        F: Fn(&NodeContext) -> Option<String>,
+    {
+        let mut used: HashSet<String> = members
+            .iter()
+            .filter_map(|id| self.nodes.get(id).and_then(|n| domain_of(&n)))
+            .collect();
+        let mut deferred = Vec::new();
+
+        for id in ranked {
+            if members.len() >= want {
+                break;
+            }
+            if members.contains(&id) {
+                continue;
+            }
+            match self.nodes.get(&id).and_then(|n| domain_of(&n)) {
+                Some(d) if used.contains(&d) => deferred.push(id),
+                Some(d) => {
+                    used.insert(d);
+                    members.push(id);
+                }
+                None => members.push(id),
+            }
+        }
+
+        for id in deferred {
+            if members.len() >= want {
+                break;
+            }
+            members.push(id);
+        }
+        members
+    }
+
+    // ---------------- Zone ----------------
+
+    pub fn zones(&self) -> Vec<String> {
+        let mut zones: Vec<String> = self
+            .nodes
+            .iter()
+            .filter_map(|e| e.value().zone.clone())
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        zones.sort();
+        zones
+    }
+
+    pub fn zone_capacity(&self, zone: &str) -> ZoneCapacity {
+        let mut cap = ZoneCapacity {
+            zone: zone.to_string(),
+            ..Default::default()
+        };
+        for entry in self.nodes.iter() {
+            let node = entry.value();
+            if node.zone.as_deref() != Some(zone) {
+                continue;
+            }
+            cap.node_count += 1;
+            let eff_opi = self.calculate_effective_opi(node);
+            if Self::is_schedulable_opi(eff_opi) {
+                cap.schedulable_nodes += 1;
+                cap.total_effective_opi += eff_opi;
+            }
+        }
+        cap
+    }
+
+    fn is_replica_host_alive(&self, node_id: &str) -> bool {
+        self.nodes
+            .get(node_id)
+            .map(|n| !n.is_quarantined && n.health_state != HealthState::Quarantined)
+            .unwrap_or(false)
+    }
+
+    fn repair_replica_sets(&self) -> Vec<(String, String)> {
+        let mut replaced = Vec::new();
+        let container_ids: Vec<String> =
+            self.replica_sets.iter().map(|e| e.key().clone()).collect();
+
+        for container_id in container_ids {
+            let Some(container) = self.get_container(&container_id) else {
+                // 레지스트리에서 사라진 컨테이너는 추적 중단
+                self.replica_sets.remove(&container_id);
+                continue;
+            };
+            let required = self.required_replicas(&container);
+
+            let Some(members) = self.replica_sets.get(&container_id).map(|m| m.clone()) else {
+                continue;
+            };
+            let alive: Vec<String> = members
+                .into_iter()
+                .filter(|id| self.is_replica_host_alive(id))
+                .collect();
+            if alive.len() >= required as usize {
+                self.replica_sets.insert(container_id, alive);
+                continue;
+            }
+
+            let before = alive.clone();
+            let refilled = self.fill_replica_set(&container, required, alive);
+            for node_id in refilled.iter().filter(|id| !before.contains(id)) {
+                info!("[Replica] {} re-placed on {}", container_id, node_id);
+                replaced.push((container_id.clone(), node_id.clone()));
+            }
+            if refilled.len() < required as usize {
+                warn!(
+                    "[Replica] {} under-replicated: {}/{} node(s)",
+                    container_id,
+                    refilled.len(),
+                    required
+                );
+            }
+            self.replica_sets.insert(container_id, refilled);
+        }
+
+        replaced
+    }
+
+    // ---------------- 정합성 점검 ----------------
+
+    // 노드 목록을 기준(ground truth)으로 나머지 상태를 맞춘다
+    pub fn reconcile(&self) -> ReconcileReport {
+        let now = self.clock.now();
+        let timeout = self.config.pending_ack_timeout_secs;
+        let mut report = ReconcileReport::default();
+        // Heartbeat 경로는 nodes 락을 잡은 채 node_stats 등을 잠그므로,
+        // 여기서 다른 맵을 잠근 채 nodes를 조회하면 락 순서가 뒤집혀 교착될 수 있다
+        let live: HashSet<String> = self.nodes.iter().map(|e| e.key().clone()).collect();
+
+        // 등록과 제거가 겹치면 인덱스가 nodes와 어긋날 수 있으므로 여기서 맞춘다
+        {
+            let mut index = self.node_index.write();
+            index.retain(|id| live.contains(id));
+            if index.len() != live.len() {
+                *index = live.iter().cloned().collect();
+            }
+        }
+
+        self.assignments.write().retain(|_, a| {
+            if !live.contains(&a.node_id) {
+                report.dangling_assignments.push(a.shard_id.clone());
+                return false;
+            }
+            if a.status == AssignmentStatus::Pending
+                && now.saturating_duration_since(a.created_at).as_secs_f64() > timeout
+            {
+                report.expired_pending.push(a.shard_id.clone());
+                return false;
+            }
+            true
+        });
+
+        for mut entry in self.replica_sets.iter_mut() {
+            let container_id = entry.key().clone();
+            entry.value_mut().retain(|id| {
+                let exists = live.contains(id);
+                if !exists {
+                    report
+                        .pruned_replica_members
+                        .push((container_id.clone(), id.clone()));
+                }
+                exists
+            });
+        }
+
+        let side_len = || {
+            self.node_stats.len()
+                + self.priority_boosts.len()
+                + self.rejection_penalties.len()
+                + self.warm_start_scores.len()
+        };
+        let before = side_len();
+        self.node_stats.retain(|id, _| live.contains(id));
+        self.priority_boosts.retain(|id, _| live.contains(id));
+        self.rejection_penalties.retain(|id, _| live.contains(id));
+        self.warm_start_scores.retain(|id, _| live.contains(id));
+        report.orphaned_side_entries = before - side_len();
+
+        for mut sessions in self.client_sessions.iter_mut() {
+            for session in sessions.value_mut().iter_mut() {
+                if session
+                    .node_id
+                    .as_ref()
+                    .is_some_and(|id| !live.contains(id))
+                {
+                    session.node_id = None;
+                    report.orphaned_side_entries += 1;
+                }
+            }
+        }
+
+        if !report.is_clean() {
+            warn!(
+                "[Reconcile] dangling={} expired_pending={} replica_pruned={} orphaned={}",
+                report.dangling_assignments.len(),
+                report.expired_pending.len(),
+                report.pruned_replica_members.len(),
+                report.orphaned_side_entries
+            );
+        }
+        report
+    }
+
+    // ---------------- 주기 작업 ----------------
+
+    pub fn tick(&self) -> TickReport {
+        self.prune_unknown_heartbeat_logs();
+        metrics::sync_node_counts(
+            self.nodes
+                .iter()
+                .map(|e| (e.value().current_tier.clone(), e.value().health_state)),
+        );
+        let now = self.clock.now();
+        self.client_affinity.retain(|_, a| a.expires_at > now);
+        self.rejection_penalties.retain(|_, until| *until > now);
+        let health_changes = self.sweep_health();
+        // 복구 작업 전에 먼저 상태를 정리해야 repair가 유령 노드를 세지 않는다
+        let reconciled = self.reconcile();
+
+        TickReport {
+            health_changes,
+            reconciled,
+            replaced_replicas: self.repair_replica_sets(),
+            expired_boosts: self.expire_priority_boosts(),
+            stalled_shards: self
+                .stalled_assignments()
+                .into_iter()
+                .map(|a| a.shard_id)
+                .collect(),
+        }
+    }
+
+    // ---------------- 상태 직렬화 ----------------
+
+    pub fn export_state(&self) -> Result<Vec<u8>, StateError> {
+        let now = self.clock.now();
+        let saved_at_unix_secs = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        let nodes = self
+            .nodes
+            .iter()
+            .map(|e| {
+                let node = e.value();
+                PersistedNode::from_node(node, now, self.calculate_effective_opi(node))
+            })
+            .collect();
+
+        let state = PersistedState {
+            schema_version: STATE_SCHEMA_VERSION,
+            saved_at_unix_secs,
+            master_id: self.master_id.clone(),
+            nodes,
+        };
+        Ok(serde_json::to_vec_pretty(&state)?)
+    }
+
+    pub fn save_state(&self, path: &str) -> io::Result<()> {
+        self.persist_state(path).map_err(io::Error::from)
+    }
+
+    // 재시작 복구용. master_id는 저장된 값을 그대로 쓴다
+    pub fn load_state(path: &str, thresholds: OverloadThresholds) -> io::Result<Self> {
+        let bytes = fs::read(path)?;
+        let state = Self::decode_state(&bytes)?;
+        let scheduler = Self::new(
+            state.master_id.clone(),
+            thresholds,
+            HealthThresholds::default(),
+        );
+        scheduler.restore_nodes(state);
+        Ok(scheduler)
+    }
+
+    pub fn persist_state(&self, path: &str) -> Result<(), StateError> {
+        let bytes = self.export_state()?;
+        self.write_state_bytes(&FileStateStore::new(path), &bytes)
+    }
+
+    pub fn save_state_to_store(&self) -> Result<(), StateError> {
+        let bytes = self.export_state()?;
+        self.write_state_bytes(self.state_store.as_ref(), &bytes)
+    }
+
+    // 저장된 상태가 없으면 Ok(0)
+    pub fn load_state_from_store(&self) -> Result<usize, StateError> {
+        match self.state_store.load() {
+            Some(bytes) => self.import_state(&bytes),
+            None => Ok(0),
+        }
+    }
+
+    fn write_state_bytes(&self, store: &dyn StateStore, bytes: &[u8]) -> Result<(), StateError> {
+        let attempts = self.config.state_write_attempts;
+        let backoff = self.config.state_write_backoff_ms;
+        let cause = match write_state_with_retry(|b| store.save(b), bytes, attempts, backoff) {
+            Ok(()) => return Ok(()),
+            Err(e) => e,
+        };
+
+        // 최후 수단: fallback 디렉터리에 파일로 한 번 더 시도
+        let fallback = self
+            .config
+            .state_fallback_dir
+            .clone()
+            .unwrap_or_else(std::env::temp_dir)
+            .join(STATE_FALLBACK_FILE);
+        error!(
+            "[State] Giving up on {:?} after {} attempt(s): {}; trying {}",
+            store,
+            attempts.max(1),
+            cause,
+            fallback.display()
+        );
+        match write_state_atomic(&fallback, bytes) {
+            Ok(()) => Err(StateError::WrittenToFallback { fallback, cause }),
+            Err(e) => {
+                error!("[State] Fallback write failed: {}", e);
+                Err(StateError::Io(cause))
+            }
+        }
+    }
+
+    // 버전을 먼저 확인하고 나서 본문을 해석한다 (새 버전 파일을 옛 구조로 억지로 읽지 않도록)
+    pub fn decode_state(bytes: &[u8]) -> Result<PersistedState, StateError> {
+        let mut value: serde_json::Value = serde_json::from_slice(bytes)?;
+        let found = value
+            .get("schema_version")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(1) as u32;
+
+        if found > STATE_SCHEMA_VERSION {
+            return Err(StateError::UnsupportedVersion {
+                found,
+                supported: STATE_SCHEMA_VERSION,
+            });
+        }
+        if found < STATE_SCHEMA_VERSION {
+            info!(
+                "[State] Upgrading schema v{} -> v{}",
+                found, STATE_SCHEMA_VERSION
+            );
+        }
+        if found < 2 {
+            Self::migrate_state_v1_to_v2(&mut value);
+        }
+
+        let mut state: PersistedState = serde_json::from_value(value)?;
+        state.schema_version = STATE_SCHEMA_VERSION;
+        Ok(state)
+    }
+
+    // v1 노드에는 v2에서 추가된 필드가 없다: 기존 동작과 같은 기본값으로 채운다
+    // (VRAM 0 = 미보고, 드레인/capability 오류 없음, 저장 OPI 없음)
+    fn migrate_state_v1_to_v2(value: &mut serde_json::Value) {
+        let Some(nodes) = value.get_mut("nodes").and_then(|n| n.as_array_mut()) else {
+            return;
+        };
+        for node in nodes.iter_mut().filter_map(|n| n.as_object_mut()) {
+            let defaults = [
+                ("zone", serde_json::Value::Null),
+                ("cost_per_hour", serde_json::Value::Null),
+                ("storage_speed_mbps", 0.0.into()),
+                ("cached_models", serde_json::json!([])),
+                ("advertised_models", serde_json::json!([])),
+                ("capability_invalid", false.into()),
+                ("total_vram_gb", 0.into()),
+                ("healthy_uptime_secs", 0.0.into()),
+                ("failure_domain", serde_json::Value::Null),
+                ("is_draining", false.into()),
+                ("last_effective_opi", 0.0.into()),
+            ];
+            for (key, default) in defaults {
+                node.entry(key).or_insert(default);
+            }
+        }
+        value["schema_version"] = 2.into();
+    }
+
+    // 복원된 노드 수를 반환
+    pub fn import_state(&self, bytes: &[u8]) -> Result<usize, StateError> {
+        let state = Self::decode_state(bytes)?;
+        Ok(self.restore_nodes(state))
+    }
+
+    fn restore_nodes(&self, state: PersistedState) -> usize {
+        let _membership = self.membership.lock();
+        let now = self.clock.now();
+        let mut count = 0;
+
+        for persisted in state.nodes {
+            // 이미 등록된 노드는 저장본보다 최신이므로 덮어쓰지 않는다 (node_stats도 유지)
+            if self.nodes.contains_key(&persisted.node_id) {
+                debug!(
+                    "[State] Keeping live {} instead of its saved copy",
+                    persisted.node_id
+                );
+                continue;
+            }
+            let mut node = persisted.into_node(now);
+            // 저장 파일도 외부 입력이므로 register_node_ctx와 같은 검증/상한을 거친다
+            if let Err(e) = self
+                .validate_hardware(&mut node)
+                .and_then(|_| self.ensure_capacity_for(&node.node_id))
+            {
+                warn!("[State] Skipping restore of {}: {}", node.node_id, e);
+                continue;
+            }
+            // 살아있는지 모르는 노드: 새 하트비트가 올 때까지 Suspect로 두어 배정 대상에서 뺀다
+            // (seed_warm_start로 임시 점수를 받은 노드만 예외, 격리 노드는 그대로 제외)
+            if node.health_state != HealthState::Quarantined {
+                node.health_state = HealthState::Suspect;
+            }
+            let mut stats = NodeStats::new(now, &node);
+            // 저장된 EMA는 이미 수렴한 값으로 본다
+            if node.net_rtt_ema_ms > 0.0 {
+                stats.rtt_samples = self.config.rtt_min_samples;
+            }
+            self.node_stats.insert(node.node_id.clone(), stats);
+            metrics::node_added(&node.current_tier, node.health_state);
+            let registered = SchedulerEvent::NodeRegistered {
+                node_id: node.node_id.clone(),
+                tier: node.current_tier.clone(),
+            };
+            let node_id = node.node_id.clone();
+            self.nodes.insert(node_id.clone(), node);
+            self.node_index.write().push(node_id);
+            self.emit_event(registered);
+            count += 1;
+        }
+
+        info!(
+            "[State] Imported {} node(s) saved by {}",
+            count, state.master_id
+        );
+        count
+    }
+
+    // ---------------- Offload / Admin ----------------
+
+    // (작업을 보낼 node_id, 클라이언트에 보낼 OffloadAccepted). 받을 노드가 없으면 거절 사유
+    pub fn handle_offload_request(
+        &self,
+        client_id: &str,
         req: &OffloadRequestPayload,
-    ) -> Option<ServerCommand> {
+    ) -> Result<(String, ServerCommand), PlacementError> {
         info!(
             "[Offload] {} -> container={} task_type={} model={}",
             client_id, req.container_id, req.task_type, req.model_variant
         );
 
-        Some(ServerCommand {
-            r#type: ServerCmdType::OffloadAccepted as i32,
-            task_id: format!("offload_{}", client_id),
-            payload: None,
-        })
+        let task_id = format!("offload_{}", client_id);
+        let Some(node_id) = self.place_offload(client_id, &req.container_id) else {
+            warn!(
+                "[Offload] No capable node for container={} (client={})",
+                req.container_id, client_id
+            );
+            return Err(self.placement_failure(&req.container_id));
+        };
+        self.track_client_session(
+            client_id,
+            &task_id,
+            SessionKind::Offload,
+            Some(req.container_id.clone()),
+            Some(node_id.clone()),
+        );
+
+        let cmd = ServerCommand {
+            r#type: ServerCmdType::OffloadAccepted as i32,
+            task_id,
+            payload: None,
+        };
+        Ok((node_id, cmd))
+    }
+
+    // 레지스트리에 있는 컨테이너면 영구 불가능/일시 부족을 구분, 모르는 컨테이너는 일시 부족
+    fn placement_failure(&self, container_id: &str) -> PlacementError {
+        let reason = self
+            .get_container(container_id)
+            .and_then(|c| self.infeasibility(&c));
+        match reason {
+            Some(reason) => PlacementError::Infeasible {
+                container_id: container_id.to_string(),
+                reason,
+            },
+            None => PlacementError::NoCapacity(container_id.to_string()),
+        }
+    }
+
+    // (스트림을 받을 node_id, StreamInit). RTT 상한 안에 노드가 없으면 거절
+    pub fn handle_babel_request(
+        &self,
+        client_id: &str,
+        _req: &stc::BabelRequestPayload,
+    ) -> Result<(String, ServerCommand), PlacementError> {
+        info!("[Babel] Session start: client={}", client_id);
+
+        let task_id = format!("babel_{}", client_id);
+        let Some((node_id, rtt_ms)) = self.place_babel_stream() else {
+            warn!(
+                "[Babel] No streaming node under {:.0}ms RTT for client={}; rejecting",
+                self.config.babel_max_rtt_ms, client_id
+            );
+            return Err(PlacementError::NoCapacity("Babel".to_string()));
+        };
+        info!(
+            "[Babel] client={} -> node={} (rtt {:.1}ms)",
+            client_id, node_id, rtt_ms
+        );
+        self.track_client_session(
+            client_id,
+            &task_id,
+            SessionKind::Babel,
+            None,
+            Some(node_id.clone()),
+        );
+
+        let cmd = ServerCommand {
+            r#type: ServerCmdType::StreamInit as i32,
+            task_id,
+            payload: None,
+        };
+        Ok((node_id, cmd))
+    }
+
+    // 스트리밍은 처리량보다 지연이 중요하므로 후보 중 RTT EMA가 가장 낮은 노드.
+    // RTT가 아직 측정되지 않은 노드는 측정된 노드가 없을 때만 OPI 순위로 사용
+    fn place_babel_stream(&self) -> Option<(String, f64)> {
+        let candidates = match self.container_for_domain("Babel") {
+            Some(container) => self.find_candidates_for_container(&container),
+            None => self.find_smart_candidates(),
+        };
+        let ceiling = self.config.babel_max_rtt_ms;
+
+        let mut unmeasured = None;
+        let mut any_measured = false;
+        let mut best: Option<(String, f64)> = None;
+        for id in candidates {
+            let Some(rtt) = self.nodes.get(&id).map(|n| n.net_rtt_ema_ms) else {
+                continue;
+            };
+            if rtt <= 0.0 {
+                unmeasured.get_or_insert((id, rtt));
+                continue;
+            }
+            any_measured = true;
+            if rtt <= ceiling && best.as_ref().is_none_or(|b| rtt < b.1) {
+                best = Some((id, rtt));
+            }
+        }
+        // 측정된 노드가 모두 상한을 넘었다면 미측정 노드로 대신하지 않고 거절한다
+        if any_measured {
+            best
+        } else {
+            unmeasured
+        }
+    }
+
+    // Offload와 같은 배치 경로 (예약/soft affinity 포함). Assist 도메인 컨테이너가 없으면 일반 순위.
+    // 프로토콜에 Assist 전용 명령이 없으므로 OffloadAccepted로 응답
+    pub fn handle_assist_request(
+        &self,
+        client_id: &str,
+        _req: &stc::AssistRequestPayload,
+    ) -> Result<(String, ServerCommand), PlacementError> {
+        info!("[Assist] Request: client={}", client_id);
+
+        let task_id = format!("assist_{}", client_id);
+        let container_id = self
+            .container_for_domain("Assist")
+            .map(|c| c.id)
+            .unwrap_or_default();
+        let Some(node_id) = self.place_offload(client_id, &container_id) else {
+            warn!("[Assist] No capable node for client={}", client_id);
+            return Err(self.placement_failure(&container_id));
+        };
+        info!("[Assist] client={} -> node={}", client_id, node_id);
+        self.track_client_session(
+            client_id,
+            &task_id,
+            SessionKind::Assist,
+            (!container_id.is_empty()).then(|| container_id.clone()),
+            Some(node_id.clone()),
+        );
+
+        let cmd = ServerCommand {
+            r#type: ServerCmdType::OffloadAccepted as i32,
+            task_id,
+            payload: None,
+        };
+        Ok((node_id, cmd))
+    }
+
+    fn container_for_domain(&self, domain: &str) -> Option<ContainerInfo> {
+        self.container_registry
+            .read()
+            .iter()
+            .find(|c| c.domain.eq_ignore_ascii_case(domain))
+            .cloned()
+    }
+
+    // 같은 클라이언트의 연속 Offload는 이전 노드를 우선 (캐시 재사용).
+    // 그 노드가 후보에서 빠졌으면(과부하/비정상) 일반 순위로 대체
+    fn place_offload(&self, client_id: &str, container_id: &str) -> Option<String> {
+        let _reservation = self.reservation.lock();
+        let (candidates, evaluated) = match self.get_container(container_id) {
+            Some(container) => self.find_candidates_for_container_evaluated(&container),
+            None => self.find_smart_candidates_evaluated(),
+        };
+        let now = self.clock.now();
+
+        let preferred = self
+            .client_affinity
+            .get(client_id)
+            .filter(|a| self.config.client_affinity_ttl_secs > 0.0 && a.expires_at > now)
+            .map(|a| a.node_id.clone())
+            .filter(|id| candidates.contains(id));
+        let chosen = preferred.or_else(|| candidates.into_iter().next());
+
+        let events = chosen
+            .iter()
+            .map(|id| {
+                let event = HistoryEvent::ChosenForOffload {
+                    container_id: container_id.to_string(),
+                };
+                (id.clone(), event)
+            })
+            .collect();
+        self.record_decision_pass(evaluated, &events);
+
+        let chosen = chosen?;
+        if let Some(mut node) = self.nodes.get_mut(&chosen) {
+            node.pending_reservations = node.pending_reservations.saturating_add(1);
+            node.reserved_at = Some(now);
+        }
+
+        let ttl = Duration::from_secs_f64(self.config.client_affinity_ttl_secs.max(0.0));
+        self.client_affinity.insert(
+            client_id.to_string(),
+            ClientAffinity {
+                node_id: chosen.clone(),
+                expires_at: now + ttl,
+            },
+        );
+        Some(chosen)
+    }
+
+    // Offload 작업 완료/거절 시 호출 (Shard 거절은 스케줄러가 직접 호출). 예약이 없으면 false
+    pub fn release_reservation(&self, id: &str) -> bool {
+        let Some(mut node) = self.nodes.get_mut(id) else {
+            return false;
+        };
+        if node.pending_reservations == 0 {
+            return false;
+        }
+        node.pending_reservations -= 1;
+        if node.pending_reservations == 0 {
+            node.reserved_at = None;
+        }
+        true
+    }
+
+    // ---------------- Client 세션 ----------------
+
+    fn track_client_session(
+        &self,
+        client_id: &str,
+        task_id: &str,
+        kind: SessionKind,
+        container_id: Option<String>,
+        node_id: Option<String>,
+    ) {
+        self.client_sessions
+            .entry(client_id.to_string())
+            .or_default()
+            .push(ClientSession {
+                task_id: task_id.to_string(),
+                kind,
+                container_id,
+                node_id,
+                started_at: self.clock.now(),
+            });
+    }
+
+    pub fn client_sessions(&self, client_id: &str) -> ClientSessionView {
+        let mut view = ClientSessionView {
+            client_id: client_id.to_string(),
+            ..Default::default()
+        };
+        let Some(sessions) = self.client_sessions.get(client_id) else {
+            return view;
+        };
+
+        let now = self.clock.now();
+        for session in sessions.iter() {
+            let item = SessionView {
+                task_id: session.task_id.clone(),
+                container_id: session.container_id.clone(),
+                node_id: session.node_id.clone(),
+                status: if session.node_id.is_some() {
+                    SessionStatus::Assigned
+                } else {
+                    SessionStatus::Unassigned
+                },
+                age_secs: now
+                    .saturating_duration_since(session.started_at)
+                    .as_secs_f64(),
+            };
+            match session.kind {
+                SessionKind::Babel => view.babel_sessions.push(item),
+                SessionKind::Offload => view.offloads.push(item),
+                SessionKind::Assist => view.assists.push(item),
+            }
+        }
+        view
+    }
+
+    // 클라이언트 연결 종료 시: 세션/오프로드를 정리하고 관련 할당을 해제.
+    // 프로토콜에 취소 명령이 없으므로 정리된 세션을 돌려주고, 노드 통지는 전송 계층이 한다
+    pub fn cancel_client(&self, client_id: &str) -> Vec<ClientSession> {
+        self.client_affinity.remove(client_id);
+        let Some((_, sessions)) = self.client_sessions.remove(client_id) else {
+            return Vec::new();
+        };
+
+        for session in &sessions {
+            self.assignments
+                .write()
+                .retain(|_, a| a.task_id != session.task_id);
+            // Offload/Assist는 place_offload에서 예약을 잡았다
+            if matches!(session.kind, SessionKind::Offload | SessionKind::Assist) {
+                if let Some(node_id) = &session.node_id {
+                    self.release_reservation(node_id);
+                }
+            }
+        }
+
+        info!(
+            "[Client] Cancelled {}: {} session(s) torn down",
+            client_id,
+            sessions.len()
+        );
+        sessions
+    }
+
+    pub fn handle_admin_action(&self, req: &AdminRequestPayload) -> Result<String, AdminError> {
+        info!(
+            "[Admin] Action: {} target={} msg={}",
+            req.action, req.target, req.message
+        );
+
+        if !ADMIN_ACTIONS.contains(&req.action.as_str()) {
+            return Err(AdminError::UnsupportedAction(req.action.clone()));
+        }
+        // 현재 지원하는 액션은 모두 노드 대상
+        if req.target.trim().is_empty() {
+            return Err(AdminError::InvalidArgument(format!(
+                "'{}' requires a target node id",
+                req.action
+            )));
+        }
+
+        match req.action.as_str() {
+            "quarantine" => self.admin_quarantine(&req.target),
+            "unquarantine" => self.admin_unquarantine(&req.target),
+            "evict" => self.admin_evict(&req.target),
+            other => Err(AdminError::UnsupportedAction(other.to_string())),
+        }
+    }
+
+    fn admin_quarantine(&self, id: &str) -> Result<String, AdminError> {
+        let mut node = self
+            .nodes
+            .get_mut(id)
+            .ok_or_else(|| AdminError::UnknownNode(id.to_string()))?;
+        if let Some(mut stats) = self.node_stats.get_mut(id) {
+            stats.admin_quarantined = true;
+        }
+        let before = node.health_state;
+        node.health_state = HealthState::Quarantined;
+        node.is_quarantined = true;
+        self.note_health_change(id, before, node.health_state);
+        warn!("[Admin] {} quarantined by operator", id);
+        Ok(format!("{} quarantined", id))
+    }
+
+    // 관리자 격리와 연속 실패 카운트만 해제하고, 나머지(타임아웃/퇴행)는 평소대로 판정
+    fn admin_unquarantine(&self, id: &str) -> Result<String, AdminError> {
+        let mut node = self
+            .nodes
+            .get_mut(id)
+            .ok_or_else(|| AdminError::UnknownNode(id.to_string()))?;
+        if let Some(mut stats) = self.node_stats.get_mut(id) {
+            stats.admin_quarantined = false;
+        }
+        node.failure_count = 0;
+        self.update_health_state(&mut node);
+        info!("[Admin] {} unquarantined (now {:?})", id, node.health_state);
+        Ok(format!("{} unquarantined, now {:?}", id, node.health_state))
+    }
+
+    fn admin_evict(&self, id: &str) -> Result<String, AdminError> {
+        let _membership = self.membership.lock();
+        self.remove_node_state(id)
+            .ok_or_else(|| AdminError::UnknownNode(id.to_string()))?;
+        // 남은 Assignment/replica 멤버는 다음 reconcile에서 정리됨
+        warn!("[Admin] {} evicted by operator", id);
+        Ok(format!("{} evicted", id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn thresholds() -> OverloadThresholds {
+        OverloadThresholds {
+            cpu_max: 0.8,
+            gpu_max: 0.8,
+            vram_pressure_max: 0.9,
+        }
+    }
+
+    fn scheduler_with(config: SchedulerConfig, clock: MockClock) -> StcScheduler {
+        StcScheduler::builder("master-test", thresholds())
+            .with_config(config)
+            .with_clock(clock)
+            .with_container_path("/nonexistent/stc-test-containers.json")
+            .build()
+            .expect("test config is valid")
+    }
+
+    fn scheduler(config: SchedulerConfig) -> StcScheduler {
+        scheduler_with(config, MockClock::new())
+    }
+
+    fn scheduler_with_weights(weights: OpiWeights) -> StcScheduler {
+        StcScheduler::builder("master-test", thresholds())
+            .with_opi_weights(weights)
+            .with_clock(MockClock::new())
+            .with_container_path("/nonexistent/stc-test-containers.json")
+            .build()
+            .expect("test config is valid")
+    }
+
+    // 데스크톱급 GPU 노드 (기본 경계에서 상위 Tier)
+    fn node(id: &str) -> NodeContext {
+        NodeContext {
+            node_id: id.to_string(),
+            device_model: "test-rig".to_string(),
+            cpu_cores: 16,
+            total_ram_mb: 32 * 1024,
+            has_npu: false,
+            has_cuda: true,
+            has_rocm: false,
+            has_intel_arc: false,
+            pcie_lanes: 16,
+            pcie_gen: 4,
+            memory_bandwidth_gbps: 400.0,
+            compute_units: 60,
+            current_tier: NodeTier::Offline,
+            last_seen: Instant::now(),
+            cpu_load: 0.1,
+            gpu_load: 0.1,
+            is_charging: true,
+            network_type: "ethernet".to_string(),
+            user_allowed: true,
+            zone: None,
+            cost_per_hour: None,
+            storage_speed_mbps: 0.0,
+            cached_models: Vec::new(),
+            advertised_models: HashSet::new(),
+            net_rtt_ema_ms: 0.0,
+            health_state: HealthState::Healthy,
+            failure_count: 0,
+            is_quarantined: false,
+            capability_invalid: false,
+            total_vram_gb: 24,
+            healthy_uptime_secs: 0.0,
+            failure_domain: None,
+            is_draining: false,
+            pending_reservations: 0,
+            reserved_at: None,
+            network_kind: NetworkKind::default(),
+        }
+    }
+
+    fn container(id: &str) -> ContainerInfo {
+        ContainerInfo {
+            id: id.to_string(),
+            name: id.to_string(),
+            domain: "vision".to_string(),
+            ai_models: Vec::new(),
+            description: String::new(),
+            status: "ready".to_string(),
+            required_vram_gb: 0,
+            min_replicas: 0,
+            required_accelerator: AcceleratorKind::Any,
+            model_size_gb: 0.0,
+            communicates_with: Vec::new(),
+            sla: SlaClass::default(),
+            resource_profile: ResourceProfile::default(),
+        }
+    }
+
+    fn quarantine(sched: &StcScheduler, id: &str) {
+        for _ in 0..sched.health_thresholds.max_consecutive_failures {
+            sched.record_node_failure(id);
+        }
+        assert_eq!(
+            sched.nodes.get(id).unwrap().health_state,
+            HealthState::Quarantined
+        );
+    }
+
+    fn shard_of(cmd: &ServerCommand) -> &stc::ShardPayload {
+        match &cmd.payload {
+            Some(ServerPayload::Shard(shard)) => shard,
+            other => panic!("not a shard command: {other:?}"),
+        }
+    }
+
+    fn ack(node_id: &str, cmd: &ServerCommand, accepted: bool) -> AckPayload {
+        let shard = shard_of(cmd);
+        AckPayload {
+            node_id: node_id.to_string(),
+            task_id: cmd.task_id.clone(),
+            shard_index: shard.shard_index,
+            shard_id: shard.shard_id.clone(),
+            accepted,
+            reason: if accepted {
+                String::new()
+            } else {
+                "busy".to_string()
+            },
+        }
+    }
+
+    fn reject(node_id: &str, cmd: &ServerCommand, reason: &str) -> ShardRejectPayload {
+        let shard = shard_of(cmd);
+        ShardRejectPayload {
+            node_id: node_id.to_string(),
+            task_id: cmd.task_id.clone(),
+            shard_index: shard.shard_index,
+            shard_id: shard.shard_id.clone(),
+            reason: reason.to_string(),
+        }
+    }
+
+    fn heartbeat(sched: &StcScheduler, id: &str, cpu: f64, gpu: f64, rtt_ms: f64) {
+        sched.update_node_status_with_rtt(id, cpu, gpu, true, "ethernet".to_string(), true, rtt_ms);
+    }
+
+    fn offload_request(container_id: &str) -> OffloadRequestPayload {
+        OffloadRequestPayload {
+            container_id: container_id.to_string(),
+            task_type: "inference".to_string(),
+            model_variant: "default".to_string(),
+        }
+    }
+
+    fn progress(node_id: &str, cmd: &ServerCommand, value: f64) -> ProgressPayload {
+        let shard = shard_of(cmd);
+        ProgressPayload {
+            node_id: node_id.to_string(),
+            task_id: cmd.task_id.clone(),
+            shard_index: shard.shard_index,
+            shard_id: shard.shard_id.clone(),
+            progress: value,
+        }
+    }
+
+    #[test]
+    fn fast_path_evaluates_only_the_sample() {
+        let sched = scheduler(SchedulerConfig {
+            fast_path_fleet_threshold: 50,
+            fast_path_sample_size: 8,
+            fast_path_budget_ms: 1_000.0,
+            ..Default::default()
+        });
+        for i in 0..2_000 {
+            sched.register_node_ctx(node(&format!("n{i}"))).unwrap();
+        }
+
+        let (ranked, evaluated) =
+            sched.rank_candidates_evaluated(ResourceProfile::Combined, |_| Some(1.0));
+
+        assert_eq!(evaluated.len(), 8);
+        let distinct: HashSet<&String> = evaluated.iter().map(|(id, _)| id).collect();
+        assert_eq!(distinct.len(), 8);
+        assert!(!ranked.is_empty() && ranked.len() <= 8);
+    }
+
+    #[test]
+    fn fast_path_never_samples_removed_nodes() {
+        let clock = MockClock::new();
+        let sched = scheduler_with(
+            SchedulerConfig {
+                fast_path_fleet_threshold: 10,
+                fast_path_sample_size: 64,
+                ..Default::default()
+            },
+            clock.clone(),
+        );
+        for i in 0..100 {
+            sched.register_node_ctx(node(&format!("n{i}"))).unwrap();
+        }
+        clock.advance_secs(120.0);
+        for i in 0..50 {
+            sched.touch_node(&format!("n{i}"));
+        }
+        assert_eq!(sched.evict_stale_nodes(60.0).len(), 50);
+        assert_eq!(sched.node_index.read().len(), sched.nodes.len());
+
+        for id in sched.sample_node_ids(64) {
+            assert!(sched.nodes.contains_key(&id), "sampled evicted node {id}");
+        }
+    }
+
+    #[test]
+    fn negative_scoring_never_yields_negative_effective_opi() {
+        let weights = OpiWeights {
+            compute_per_unit: -5.0,
+            ..Default::default()
+        };
+        let sched = scheduler_with_weights(weights);
+        let mut negative = node("negative");
+        negative.compute_units = 200;
+        sched.register_node_ctx(negative).unwrap();
+        sched.register_node_ctx(node("positive")).unwrap();
+
+        assert!(sched.calculate_raw_opi(&sched.nodes.get("negative").unwrap()) < 0.0);
+        for (id, score) in sched.effective_opi_all() {
+            assert!(score >= 0.0, "{id} scored {score}");
+        }
+        assert_eq!(sched.find_smart_candidates(), vec!["positive".to_string()]);
+        let top = sched.top_nodes(10);
+        assert_eq!(top.len(), 1);
+        assert_eq!(top[0].0, "positive");
+    }
+
+    #[test]
+    fn replicas_fill_fully_then_partially() {
+        let sched = scheduler(SchedulerConfig::default());
+        for id in ["a", "b", "c"] {
+            sched.register_node_ctx(node(id)).unwrap();
+        }
+        let mut three = container("three");
+        three.min_replicas = 3;
+        let mut five = container("five");
+        five.min_replicas = 5;
+        sched.register_containers(vec![three, five]).unwrap();
+
+        let full = sched.place_replicas("three").unwrap();
+        assert!(full.is_complete());
+        assert_eq!(full.node_ids.iter().collect::<HashSet<_>>().len(), 3);
+
+        let partial = sched.place_replicas("five").unwrap();
+        assert!(!partial.is_complete());
+        assert_eq!(partial.required, 5);
+        assert_eq!(partial.node_ids.len(), 3);
+    }
+
+    #[test]
+    fn replica_is_replaced_after_its_node_is_quarantined() {
+        let sched = scheduler(SchedulerConfig::default());
+        for id in ["a", "b", "c"] {
+            sched.register_node_ctx(node(id)).unwrap();
+        }
+        let mut pair = container("pair");
+        pair.min_replicas = 2;
+        sched.register_containers(vec![pair]).unwrap();
+
+        let placed = sched.place_replicas("pair").unwrap();
+        let victim = placed.node_ids[0].clone();
+        quarantine(&sched, &victim);
+
+        let report = sched.tick();
+        let members = sched.replica_sets.get("pair").unwrap().clone();
+        assert_eq!(members.len(), 2);
+        assert!(!members.contains(&victim));
+        assert_eq!(report.replaced_replicas.len(), 1);
+        assert_eq!(report.replaced_replicas[0].0, "pair");
+    }
+
+    #[test]
+    fn boosted_node_is_preferred_until_the_boost_expires() {
+        let clock = MockClock::new();
+        let sched = scheduler_with(SchedulerConfig::default(), clock.clone());
+        sched.register_node_ctx(node("strong")).unwrap();
+        let mut weak = node("weak");
+        weak.compute_units = 20;
+        sched.register_node_ctx(weak).unwrap();
+        assert_eq!(sched.find_smart_candidates()[0], "strong");
+
+        sched.set_priority_boost("weak", 10.0, Some(clock.now() + Duration::from_secs(60)));
+        assert_eq!(sched.find_smart_candidates()[0], "weak");
+
+        clock.advance_secs(61.0);
+        sched.touch_node("strong");
+        sched.touch_node("weak");
+        assert_eq!(sched.find_smart_candidates()[0], "strong");
+        assert_eq!(sched.tick().expired_boosts, vec!["weak".to_string()]);
+    }
+
+    #[test]
+    fn non_finite_hardware_marks_capability_invalid() {
+        let sched = scheduler(SchedulerConfig::default());
+        assert_eq!(sched.determine_tier(f64::NAN), None);
+        assert_eq!(sched.determine_tier(f64::INFINITY), None);
+
+        let mut nan = node("nan");
+        nan.memory_bandwidth_gbps = f64::NAN;
+        sched.register_node_ctx(nan).unwrap();
+        sched.register_node_ctx(node("ok")).unwrap();
+
+        let bad = sched.nodes.get("nan").unwrap().clone();
+        assert!(bad.capability_invalid);
+        assert_eq!(bad.current_tier, NodeTier::Offline);
+        assert_eq!(sched.effective_opi("nan"), Some(0.0));
+        assert_eq!(sched.find_smart_candidates(), vec!["ok".to_string()]);
+
+        // 0으로 나누는 가중치는 모든 노드를 inf로 만든다
+        let weights = OpiWeights {
+            bandwidth_divisor: 0.0,
+            ..Default::default()
+        };
+        let inf_sched = scheduler_with_weights(weights);
+        inf_sched.register_node_ctx(node("inf")).unwrap();
+        assert!(inf_sched.nodes.get("inf").unwrap().capability_invalid);
+        assert!(inf_sched.find_smart_candidates().is_empty());
+    }
+
+    #[test]
+    fn ack_accept_reject_and_unknown_shard() {
+        let sched = scheduler(SchedulerConfig::default());
+        sched.register_node_ctx(node("a")).unwrap();
+        let commands = sched.shard_task("worker", 2);
+        assert_eq!(commands.len(), 2);
+        let (first_node, first) = &commands[0];
+        let (second_node, second) = &commands[1];
+        let status = |cmd: &ServerCommand| {
+            sched
+                .assignment(&cmd.task_id, shard_of(cmd).shard_index)
+                .unwrap()
+                .status
+        };
+        assert_eq!(status(first), AssignmentStatus::Pending);
+
+        assert_eq!(
+            sched.handle_ack(&ack(first_node, first, true)),
+            Ok(AssignmentStatus::Active)
+        );
+        assert_eq!(
+            sched.handle_ack(&ack(second_node, second, false)),
+            Ok(AssignmentStatus::Rejected)
+        );
+        assert_eq!(status(first), AssignmentStatus::Active);
+        assert_eq!(status(second), AssignmentStatus::Rejected);
+        // 거절된 Shard의 예약은 바로 풀린다
+        assert_eq!(sched.nodes.get("a").unwrap().pending_reservations, 1);
+        assert!(matches!(
+            sched.handle_ack(&ack(first_node, first, true)),
+            Err(AssignmentError::AlreadyResolved(_))
+        ));
+
+        let mut unknown = ack(first_node, first, true);
+        unknown.shard_id = "no-such-shard".to_string();
+        assert_eq!(
+            sched.handle_ack(&unknown),
+            Err(AssignmentError::UnknownShard("no-such-shard".to_string()))
+        );
+    }
+
+    #[test]
+    fn trust_score_separates_flaky_and_steady_nodes() {
+        let clock = MockClock::new();
+        let sched = scheduler_with(SchedulerConfig::default(), clock.clone());
+        sched.register_node_ctx(node("steady")).unwrap();
+        sched.register_node_ctx(node("flaky")).unwrap();
+
+        // steady: 10초 간격, RTT 20ms 고정 / flaky: 9초-1초 간격, RTT 10ms-200ms 왕복.
+        // trust_full_uptime_secs(기본 1시간)를 넘겨 가동 시간 항목은 둘 다 1.0
+        for i in 0..400 {
+            clock.advance_secs(1.0);
+            heartbeat(
+                &sched,
+                "flaky",
+                0.1,
+                0.1,
+                if i % 2 == 0 { 10.0 } else { 200.0 },
+            );
+            clock.advance_secs(4.0);
+            heartbeat(&sched, "steady", 0.1, 0.1, 20.0);
+            clock.advance_secs(5.0);
+            heartbeat(
+                &sched,
+                "flaky",
+                0.1,
+                0.1,
+                if i % 2 == 0 { 200.0 } else { 10.0 },
+            );
+        }
+        sched.record_node_failure("flaky");
+        sched.record_node_failure("flaky");
+
+        let steady = sched.trust_score("steady").unwrap();
+        let flaky = sched.trust_score("flaky").unwrap();
+        assert!(steady > 0.95, "steady trust {steady}");
+        assert!(flaky < 0.2, "flaky trust {flaky}");
+    }
+
+    #[test]
+    fn accelerator_requirement_skips_the_other_vendor() {
+        let sched = scheduler(SchedulerConfig::default());
+        sched.register_node_ctx(node("nvidia")).unwrap();
+        let mut amd = node("amd");
+        amd.has_cuda = false;
+        amd.has_rocm = true;
+        sched.register_node_ctx(amd).unwrap();
+
+        let mut cuda = container("cuda");
+        cuda.required_accelerator = AcceleratorKind::Cuda;
+        let mut rocm = container("rocm");
+        rocm.required_accelerator = AcceleratorKind::Rocm;
+
+        assert_eq!(
+            sched.find_candidates_for_container(&cuda),
+            vec!["nvidia".to_string()]
+        );
+        assert_eq!(
+            sched.find_candidates_for_container(&rocm),
+            vec!["amd".to_string()]
+        );
+    }
+
+    #[test]
+    fn load_history_accumulates_and_trims_at_capacity() {
+        let sched = scheduler(SchedulerConfig {
+            load_history_capacity: 4,
+            ..Default::default()
+        });
+        sched.register_node_ctx(node("a")).unwrap();
+
+        for i in 0..3 {
+            heartbeat(&sched, "a", i as f64 / 10.0, 0.0, 10.0);
+        }
+        assert_eq!(sched.node_load_history("a").len(), 3);
+
+        for i in 3..10 {
+            heartbeat(&sched, "a", i as f64 / 10.0, 0.0, 10.0);
+        }
+        let cpu: Vec<f64> = sched
+            .node_load_history("a")
+            .iter()
+            .map(|s| s.cpu_load)
+            .collect();
+        assert_eq!(cpu, vec![0.6, 0.7, 0.8, 0.9]);
+    }
+
+    #[test]
+    fn cancel_client_tears_down_babel_and_offload() {
+        let sched = scheduler(SchedulerConfig::default());
+        sched.register_node_ctx(node("a")).unwrap();
+        sched
+            .register_containers(vec![container("vision")])
+            .unwrap();
+
+        let (babel_node, _) = sched
+            .handle_babel_request("client-1", &stc::BabelRequestPayload::default())
+            .unwrap();
+        let (offload_node, _) = sched
+            .handle_offload_request("client-1", &offload_request("vision"))
+            .unwrap();
+        assert_eq!(babel_node, "a");
+        assert_eq!(offload_node, "a");
+        assert_eq!(sched.nodes.get("a").unwrap().pending_reservations, 1);
+
+        let torn_down = sched.cancel_client("client-1");
+        let kinds: Vec<SessionKind> = torn_down.iter().map(|s| s.kind).collect();
+        assert_eq!(kinds.len(), 2);
+        assert!(kinds.contains(&SessionKind::Babel));
+        assert!(kinds.contains(&SessionKind::Offload));
+        assert!(sched.client_sessions.get("client-1").is_none());
+        assert!(sched.client_affinity.get("client-1").is_none());
+        assert_eq!(sched.nodes.get("a").unwrap().pending_reservations, 0);
+        assert!(sched.cancel_client("client-1").is_empty());
+    }
+
+    #[test]
+    fn warm_start_orders_restored_nodes_until_fresh_heartbeats() {
+        let source = scheduler(SchedulerConfig::default());
+        let mut weak = node("weak");
+        weak.compute_units = 20;
+        source.register_node_ctx(node("strong")).unwrap();
+        source.register_node_ctx(weak).unwrap();
+        source.register_node_ctx(node("quarantined")).unwrap();
+        // 저장 시점에는 strong이 바빠서 weak가 앞선다
+        heartbeat(&source, "strong", 0.7, 0.7, 10.0);
+        heartbeat(&source, "weak", 0.0, 0.0, 10.0);
+        quarantine(&source, "quarantined");
+        assert_eq!(source.find_smart_candidates()[0], "weak");
+        let bytes = source.export_state().unwrap();
+
+        let restored = scheduler(SchedulerConfig::default());
+        assert_eq!(restored.import_state(&bytes).unwrap(), 3);
+        // Heartbeat도 임시 점수도 없으면 아무도 배정받지 않는다
+        assert!(restored.find_smart_candidates().is_empty());
+
+        let state = StcScheduler::decode_state(&bytes).unwrap();
+        restored.seed_warm_start(
+            state
+                .nodes
+                .iter()
+                .map(|n| (n.node_id.clone(), n.last_effective_opi))
+                .chain([("quarantined".to_string(), 1_000.0)]),
+        );
+        assert_eq!(
+            restored.find_smart_candidates(),
+            vec!["weak".to_string(), "strong".to_string()]
+        );
+
+        heartbeat(&restored, "strong", 0.0, 0.0, 10.0);
+        heartbeat(&restored, "weak", 0.0, 0.0, 10.0);
+        assert!(!restored.warm_start_scores.contains_key("strong"));
+        assert!(!restored.warm_start_scores.contains_key("weak"));
+        assert_eq!(
+            restored.find_smart_candidates(),
+            vec!["strong".to_string(), "weak".to_string()]
+        );
+    }
+
+    #[test]
+    fn unknown_node_heartbeats_are_rate_limited() {
+        let clock = MockClock::new();
+        let sched = scheduler_with(SchedulerConfig::default(), clock.clone());
+        let interval = sched.config.unknown_node_log_interval_secs;
+
+        for _ in 0..4 {
+            heartbeat(&sched, "ghost", 0.1, 0.1, 10.0);
+        }
+        assert!(!sched.touch_node("ghost"));
+        // 첫 경고 한 번만 남기고 나머지는 억제 카운트로만 센다
+        assert_eq!(sched.unknown_heartbeats.get("ghost").unwrap().suppressed, 4);
+
+        clock.advance_secs(interval + 1.0);
+        heartbeat(&sched, "ghost", 0.1, 0.1, 10.0);
+        let log = sched.unknown_heartbeats.get("ghost").unwrap();
+        assert_eq!(log.suppressed, 0);
+        assert_eq!(log.last_logged_at, clock.now());
+    }
+
+    #[test]
+    fn replicas_spread_across_zones_when_possible() {
+        let sched = scheduler(SchedulerConfig::default());
+        // 같은 zone의 두 노드가 순위상 가장 앞선다
+        for (id, zone, cu) in [("a1", "a", 80), ("a2", "a", 70), ("b1", "b", 40)] {
+            let mut n = node(id);
+            n.zone = Some(zone.to_string());
+            n.compute_units = cu;
+            sched.register_node_ctx(n).unwrap();
+        }
+        let mut pair = container("pair");
+        pair.min_replicas = 2;
+        sched.register_containers(vec![pair]).unwrap();
+
+        let placed = sched.place_replicas("pair").unwrap();
+        assert_eq!(placed.node_ids, vec!["a1".to_string(), "b1".to_string()]);
+    }
+
+    #[test]
+    fn replicas_fall_back_to_one_zone() {
+        let sched = scheduler(SchedulerConfig::default());
+        for id in ["a1", "a2"] {
+            let mut n = node(id);
+            n.zone = Some("a".to_string());
+            sched.register_node_ctx(n).unwrap();
+        }
+        let mut pair = container("pair");
+        pair.min_replicas = 2;
+        sched.register_containers(vec![pair]).unwrap();
+
+        let placed = sched.place_replicas("pair").unwrap();
+        assert!(placed.is_complete());
+        assert_eq!(placed.node_ids.len(), 2);
+    }
+
+    #[test]
+    fn shed_target_follows_the_overload_cause() {
+        let sched = scheduler(SchedulerConfig {
+            max_shard_fanout: 1,
+            ..Default::default()
+        });
+        sched.register_node_ctx(node("a")).unwrap();
+        sched.update_master_workloads(vec![
+            MasterWorkload {
+                container_id: "llm".to_string(),
+                vram_gb: 20.0,
+                cpu_share: 0.1,
+            },
+            MasterWorkload {
+                container_id: "encoder".to_string(),
+                vram_gb: 1.0,
+                cpu_share: 0.6,
+            },
+        ]);
+        let shed = |sched: &StcScheduler| {
+            let plan = sched.plan_sharding();
+            let shards: Vec<String> = plan.shards.into_iter().map(|s| s.container_id).collect();
+            (plan.cause, shards)
+        };
+
+        sched.update_master_status(0.5, 0.5, 0.95);
+        assert_eq!(
+            shed(&sched),
+            (Some(OverloadCause::Vram), vec!["llm".to_string()])
+        );
+
+        sched.update_master_status(0.95, 0.5, 0.2);
+        assert_eq!(
+            shed(&sched),
+            (Some(OverloadCause::Cpu), vec!["encoder".to_string()])
+        );
+    }
+
+    #[test]
+    fn effective_opi_all_matches_per_node_calls() {
+        let sched = scheduler(SchedulerConfig::default());
+        for (i, id) in ["a", "b", "c"].into_iter().enumerate() {
+            let mut n = node(id);
+            n.compute_units = 20 * (i as u32 + 1);
+            sched.register_node_ctx(n).unwrap();
+            heartbeat(&sched, id, 0.2 * i as f64, 0.1, 10.0 + i as f64);
+        }
+
+        let all = sched.effective_opi_all();
+        assert_eq!(all.len(), 3);
+        for (id, score) in &all {
+            assert_eq!(Some(*score), sched.effective_opi(id));
+        }
+    }
+
+    #[test]
+    fn zero_compute_unit_node_only_takes_cpu_work() {
+        let sched = scheduler(SchedulerConfig::default());
+        let mut cpu_only = node("cpu-only");
+        cpu_only.compute_units = 0;
+        cpu_only.has_cuda = false;
+        cpu_only.total_vram_gb = 0;
+        sched.register_node_ctx(cpu_only).unwrap();
+        assert!(sched.effective_opi("cpu-only").unwrap() > 0.0);
+
+        let mut cpu_bound = container("tokenizer");
+        cpu_bound.resource_profile = ResourceProfile::CpuBound;
+        let mut gpu_bound = container("llm");
+        gpu_bound.required_vram_gb = 8;
+
+        assert_eq!(
+            sched.find_candidates_for_container(&cpu_bound),
+            vec!["cpu-only".to_string()]
+        );
+        assert!(sched.find_candidates_for_container(&gpu_bound).is_empty());
+    }
+
+    #[test]
+    fn progress_is_recorded_and_stalls_are_detected() {
+        let clock = MockClock::new();
+        let sched = scheduler_with(SchedulerConfig::default(), clock.clone());
+        sched.register_node_ctx(node("a")).unwrap();
+        let commands = sched.shard_task("worker", 1);
+        let (node_id, cmd) = &commands[0];
+
+        // Ack 전에는 진행률을 받지 않는다
+        assert!(matches!(
+            sched.handle_progress(&progress(node_id, cmd, 0.1)),
+            Err(AssignmentError::NotActive(_))
+        ));
+        sched.handle_ack(&ack(node_id, cmd, true)).unwrap();
+
+        clock.advance_secs(60.0);
+        sched.handle_progress(&progress(node_id, cmd, 0.5)).unwrap();
+        let assignment = sched.assignment(&cmd.task_id, 0).unwrap();
+        assert_eq!(assignment.progress, 0.5);
+        assert_eq!(assignment.last_progress_at, Some(clock.now()));
+
+        let stall = sched.config.progress_stall_secs;
+        clock.advance_secs(stall - 1.0);
+        assert!(sched.stalled_assignments().is_empty());
+        clock.advance_secs(2.0);
+        let stalled = sched.stalled_assignments();
+        assert_eq!(stalled.len(), 1);
+        assert_eq!(stalled[0].shard_id, shard_of(cmd).shard_id);
+    }
+
+    #[test]
+    fn full_cluster_refuses_or_evicts_a_quarantined_node() {
+        let clock = MockClock::new();
+        let sched = scheduler_with(
+            SchedulerConfig {
+                max_nodes: Some(2),
+                ..Default::default()
+            },
+            clock.clone(),
+        );
+        sched.register_node_ctx(node("a")).unwrap();
+        sched.register_node_ctx(node("b")).unwrap();
+        assert_eq!(
+            sched.register_node_ctx(node("c")),
+            Err(RegistrationError::CapacityExceeded { max_nodes: 2 })
+        );
+        // 재등록은 자리를 더 차지하지 않는다
+        assert!(sched.register_node_ctx(node("a")).is_ok());
+
+        // 실패로 막 격리됐지만 아직 살아있는 노드는 밀어내지 않는다
+        let placed = sched.shard_task("worker", 2);
+        quarantine(&sched, "a");
+        assert_eq!(
+            sched.register_node_ctx(node("c")),
+            Err(RegistrationError::CapacityExceeded { max_nodes: 2 })
+        );
+
+        clock.advance_secs(sched.health_thresholds.quarantine_after_secs + 1.0);
+        heartbeat(&sched, "b", 0.1, 0.1, 5.0);
+        sched.register_node_ctx(node("c")).unwrap();
+        assert!(!sched.nodes.contains_key("a"));
+        assert!(sched.nodes.contains_key("c"));
+        assert_eq!(sched.nodes.len(), 2);
+        let on_a = placed.iter().filter(|(id, _)| id == "a").count();
+        assert_eq!(sched.all_assignments().len(), placed.len() - on_a);
+    }
+
+    #[test]
+    fn replace_and_restore_respect_max_nodes() {
+        let sched = scheduler(SchedulerConfig {
+            max_nodes: Some(2),
+            ..Default::default()
+        });
+        let report = sched.replace_nodes(vec![node("a"), node("b"), node("c")]);
+        assert_eq!(sched.nodes.len(), 2);
+        assert_eq!(report.rejected.len(), 1);
+
+        let source = scheduler(SchedulerConfig::default());
+        for id in ["x", "y", "z"] {
+            source.register_node_ctx(node(id)).unwrap();
+        }
+        let empty = scheduler(SchedulerConfig {
+            max_nodes: Some(2),
+            ..Default::default()
+        });
+        assert_eq!(
+            empty.import_state(&source.export_state().unwrap()).unwrap(),
+            2
+        );
+        assert_eq!(empty.nodes.len(), 2);
+    }
+
+    #[test]
+    fn client_session_view_reports_stream_and_offload() {
+        let clock = MockClock::new();
+        let sched = scheduler_with(SchedulerConfig::default(), clock.clone());
+        sched.register_node_ctx(node("a")).unwrap();
+        sched
+            .register_containers(vec![container("vision")])
+            .unwrap();
+
+        sched
+            .handle_babel_request("client-1", &stc::BabelRequestPayload::default())
+            .unwrap();
+        clock.advance_secs(5.0);
+        sched
+            .handle_offload_request("client-1", &offload_request("vision"))
+            .unwrap();
+
+        let view = sched.client_sessions("client-1");
+        assert_eq!(view.babel_sessions.len(), 1);
+        assert_eq!(view.offloads.len(), 1);
+        assert!(view.assists.is_empty());
+        assert_eq!(view.babel_sessions[0].age_secs, 5.0);
+        let offload = &view.offloads[0];
+        assert_eq!(offload.container_id.as_deref(), Some("vision"));
+        assert_eq!(offload.node_id.as_deref(), Some("a"));
+        assert_eq!(offload.status, SessionStatus::Assigned);
+
+        assert!(sched.client_sessions("nobody").offloads.is_empty());
+    }
+
+    #[test]
+    fn weighted_load_blend_ranks_differently_than_max() {
+        let ranking = |load_blend: LoadBlend| {
+            let sched = scheduler(SchedulerConfig {
+                load_blend,
+                ..Default::default()
+            });
+            sched.register_node_ctx(node("mixed")).unwrap();
+            sched.register_node_ctx(node("gpu-heavy")).unwrap();
+            heartbeat(&sched, "mixed", 0.5, 0.5, 10.0);
+            heartbeat(&sched, "gpu-heavy", 0.0, 0.7, 10.0);
+            sched.find_smart_candidates()
+        };
+
+        assert_eq!(ranking(LoadBlend::Max)[0], "mixed");
+        assert_eq!(
+            ranking(LoadBlend::Weighted { cpu_weight: 0.5 })[0],
+            "gpu-heavy"
+        );
+    }
+
+    #[test]
+    fn deregistering_a_node_surfaces_its_assignments() {
+        let sched = scheduler(SchedulerConfig::default());
+        sched.register_node_ctx(node("a")).unwrap();
+        let commands = sched.shard_task("worker", 2);
+        assert!(commands.iter().all(|(id, _)| id == "a"));
+        sched.register_node_ctx(node("b")).unwrap();
+
+        let orphaned = sched.deregister_node("a");
+        assert_eq!(orphaned.len(), 2);
+        assert!(sched.all_assignments().is_empty());
+
+        let replaced = sched.replace_assignments(&orphaned);
+        assert_eq!(replaced.len(), 2);
+        for (node_id, cmd) in &replaced {
+            assert_eq!(node_id, "b");
+            assert_eq!(cmd.task_id, commands[0].1.task_id);
+        }
+        assert!(sched.deregister_node("a").is_empty());
+    }
+
+    #[test]
+    fn cost_mode_prefers_a_cheaper_adequate_node() {
+        let ranking = |ranking_mode: RankingMode| {
+            let sched = scheduler(SchedulerConfig {
+                ranking_mode,
+                ..Default::default()
+            });
+            let mut fast = node("fast");
+            fast.cost_per_hour = Some(5.0);
+            let mut cheap = node("cheap");
+            cheap.compute_units = 30;
+            cheap.cost_per_hour = Some(1.0);
+            sched.register_node_ctx(fast).unwrap();
+            sched.register_node_ctx(cheap).unwrap();
+            sched.find_smart_candidates()
+        };
+
+        assert_eq!(ranking(RankingMode::Performance)[0], "fast");
+        assert_eq!(
+            ranking(RankingMode::CheapestAboveFloor {
+                min_effective_opi: 10.0
+            })[0],
+            "cheap"
+        );
+        // 하한을 못 넘는 싼 노드는 성능 순서로 밀린다
+        assert_eq!(
+            ranking(RankingMode::CheapestAboveFloor {
+                min_effective_opi: 10_000.0
+            })[0],
+            "fast"
+        );
+    }
+
+    #[test]
+    fn halved_bandwidth_is_flagged_as_regression() {
+        let sched = scheduler(SchedulerConfig {
+            capability_regression_ratio: 0.3,
+            ..Default::default()
+        });
+        sched.register_node_ctx(node("a")).unwrap();
+
+        assert_eq!(sched.update_node_capabilities("a", 380.0, 60), Some(false));
+        assert_eq!(sched.update_node_capabilities("a", 200.0, 60), Some(true));
+        assert_eq!(sched.regressed_nodes(), vec!["a".to_string()]);
+        // Flag 정책은 표시만 하고 배정은 계속한다
+        assert_eq!(sched.find_smart_candidates(), vec!["a".to_string()]);
+
+        assert!(sched.clear_capability_regression("a"));
+        assert!(sched.regressed_nodes().is_empty());
+        assert_eq!(sched.update_node_capabilities("a", 200.0, 60), Some(false));
+    }
+
+    #[test]
+    fn quarantine_policy_holds_a_regressed_node() {
+        let sched = scheduler(SchedulerConfig {
+            capability_regression_ratio: 0.3,
+            capability_regression_policy: RegressionPolicy::Quarantine,
+            ..Default::default()
+        });
+        sched.register_node_ctx(node("a")).unwrap();
+
+        assert_eq!(sched.update_node_capabilities("a", 200.0, 60), Some(true));
+        assert!(sched.find_smart_candidates().is_empty());
+        assert_eq!(
+            sched.try_rehabilitate_node("a"),
+            Err(RehabilitationError::HeldForRegression("a".to_string()))
+        );
+    }
+
+    #[test]
+    fn concurrent_tasks_with_different_shard_totals_stay_separate() {
+        let sched = scheduler(SchedulerConfig::default());
+        sched.register_node_ctx(node("a")).unwrap();
+        sched.register_node_ctx(node("b")).unwrap();
+        let two = sched.shard_task("worker", 2);
+        let three = sched.shard_task("worker", 3);
+        let (two_node, two_first) = &two[0];
+        let (three_node, three_first) = &three[0];
+        assert_ne!(two_first.task_id, three_first.task_id);
+        assert_eq!(shard_of(&three[2].1).shard_total, 3);
+
+        // 같은 shard_index라도 다른 Task의 shard_id로는 Ack할 수 없다
+        let mut crossed = ack(three_node, three_first, true);
+        crossed.shard_id = shard_of(two_first).shard_id.clone();
+        assert!(matches!(
+            sched.handle_ack(&crossed),
+            Err(AssignmentError::UnknownShard(_))
+        ));
+        sched.handle_ack(&ack(two_node, two_first, true)).unwrap();
+
+        let (reject_node, reject_cmd) = &three[1];
+        let retried = sched
+            .handle_shard_rejection(&reject(reject_node, reject_cmd, "thermal"))
+            .unwrap();
+        assert_eq!(retried.len(), 1);
+        let retry = shard_of(&retried[0].1);
+        assert_eq!(retried[0].1.task_id, three_first.task_id);
+        assert_eq!((retry.shard_index, retry.shard_total), (1, 3));
+
+        let two_status = sched.task_status(&two_first.task_id).unwrap();
+        assert_eq!((two_status.shard_total, two_status.tracked_shards), (2, 2));
+        assert_eq!((two_status.active, two_status.pending), (1, 1));
+        let three_status = sched.task_status(&three_first.task_id).unwrap();
+        assert_eq!(
+            (three_status.shard_total, three_status.tracked_shards),
+            (3, 3)
+        );
+        assert_eq!((three_status.pending, three_status.rejected), (3, 0));
+    }
+
+    #[test]
+    fn all_quarantined_raises_emergency_and_optionally_revives_one() {
+        let run = |emergency_revive_last_resort: bool| {
+            let clock = MockClock::new();
+            let sched = scheduler_with(
+                SchedulerConfig {
+                    emergency_revive_last_resort,
+                    ..Default::default()
+                },
+                clock.clone(),
+            );
+            sched.register_node_ctx(node("old")).unwrap();
+            sched.register_node_ctx(node("recent")).unwrap();
+            quarantine(&sched, "old");
+            quarantine(&sched, "recent");
+            clock.advance_secs(1.0);
+            sched.touch_node("recent");
+
+            sched.update_master_status(0.95, 0.5, 0.2);
+            let outcome = sched.check_server_overload_and_shard();
+            assert!(sched.is_capacity_emergency());
+            outcome
+                .commands
+                .into_iter()
+                .map(|(id, _)| id)
+                .collect::<Vec<_>>()
+        };
+
+        assert!(run(false).is_empty());
+        assert_eq!(run(true), vec!["recent".to_string()]);
+    }
+
+    #[test]
+    fn touching_a_node_keeps_it_alive_without_touching_load() {
+        let clock = MockClock::new();
+        let sched = scheduler_with(SchedulerConfig::default(), clock.clone());
+        sched.register_node_ctx(node("quiet")).unwrap();
+        heartbeat(&sched, "quiet", 0.4, 0.6, 5.0);
+
+        for _ in 0..10 {
+            clock.advance_secs(8.0);
+            assert!(sched.touch_node("quiet"));
+        }
+
+        let quiet = sched.nodes.get("quiet").unwrap();
+        assert_eq!(quiet.health_state, HealthState::Healthy);
+        assert_eq!((quiet.cpu_load, quiet.gpu_load), (0.4, 0.6));
+        drop(quiet);
+        assert!(!sched.touch_node("ghost"));
+    }
+
+    #[test]
+    fn warm_cache_node_wins_time_to_ready_for_a_cold_large_model() {
+        let sched = scheduler(SchedulerConfig::default());
+        let mut cold = node("cold");
+        cold.compute_units = 64;
+        cold.storage_speed_mbps = 500.0;
+        let mut warm = node("warm");
+        warm.cached_models = vec!["llm".to_string()];
+        sched.register_node_ctx(cold).unwrap();
+        sched.register_node_ctx(warm).unwrap();
+        let mut llm = container("llm");
+        llm.model_size_gb = 40.0;
+        sched.register_containers(vec![llm]).unwrap();
+
+        assert_eq!(
+            sched.best_node_for_container("llm").unwrap().node_id,
+            "cold"
+        );
+        assert_eq!(
+            sched.place_for_time_to_ready("llm"),
+            Some(("warm".to_string(), 0.0))
+        );
+    }
+
+    #[test]
+    fn all_assignments_lists_every_placed_shard() {
+        let clock = MockClock::new();
+        let sched = scheduler_with(SchedulerConfig::default(), clock.clone());
+        sched.register_node_ctx(node("a")).unwrap();
+        sched.register_node_ctx(node("b")).unwrap();
+        let vision = sched.shard_task("vision", 2);
+        clock.advance_secs(5.0);
+        let speech = sched.shard_task("speech", 1);
+        let (node_id, cmd) = &speech[0];
+        sched.handle_ack(&ack(node_id, cmd, true)).unwrap();
+
+        let all = sched.all_assignments();
+        assert_eq!(all.len(), 3);
+        for (placed_on, cmd) in vision.iter().chain(&speech) {
+            let shard = shard_of(cmd);
+            let record = all
+                .iter()
+                .find(|r| r.task_id == cmd.task_id && r.shard_index == shard.shard_index)
+                .expect("placed shard is listed");
+            assert_eq!(&record.node_id, placed_on);
+            assert_eq!(record.shard_id, shard.shard_id);
+        }
+        let speech_record = all.iter().find(|r| r.container == "speech").unwrap();
+        assert_eq!(speech_record.status, AssignmentStatus::Active);
+        assert_eq!(speech_record.age_secs, 0.0);
+        assert!(all
+            .iter()
+            .filter(|r| r.container == "vision")
+            .all(|r| r.status == AssignmentStatus::Pending && r.age_secs >= 5.0));
+    }
+
+    #[test]
+    fn ram_cap_lets_the_better_gpu_node_win() {
+        let huge_ram = || {
+            let mut n = node("huge-ram");
+            n.total_ram_mb = 512 * 1024;
+            n.compute_units = 10;
+            n
+        };
+        let gpu = || {
+            let mut n = node("gpu");
+            n.compute_units = 120;
+            n
+        };
+        let leader = |weights: OpiWeights| {
+            let sched = scheduler_with_weights(weights);
+            sched.register_node_ctx(huge_ram()).unwrap();
+            sched.register_node_ctx(gpu()).unwrap();
+            let bounded = sched.calculate_raw_opi(&sched.nodes.get("huge-ram").unwrap());
+            (sched.top_nodes(1)[0].0.clone(), bounded)
+        };
+
+        let (uncapped_leader, uncapped) = leader(OpiWeights::default());
+        let (capped_leader, capped) = leader(OpiWeights {
+            ram_cap: Some(0.3),
+            ..Default::default()
+        });
+        assert_eq!(uncapped_leader, "huge-ram");
+        assert_eq!(capped_leader, "gpu");
+        assert!(capped < uncapped / 5.0);
+    }
+
+    #[test]
+    fn voluntary_rejection_replaces_and_deprioritizes_without_touching_boost() {
+        let clock = MockClock::new();
+        let sched = scheduler_with(SchedulerConfig::default(), clock.clone());
+        sched.register_node_ctx(node("a")).unwrap();
+        sched.register_node_ctx(node("b")).unwrap();
+        sched.set_priority_boost("a", 1.2, None);
+        let placed = sched.shard_task("worker", 1);
+        let (placed_on, cmd) = &placed[0];
+        assert_eq!(placed_on, "a");
+        let shard = shard_of(cmd);
+
+        let retried = sched
+            .handle_shard_rejection(&reject("a", cmd, "thermal"))
+            .unwrap();
+        assert_eq!(retried.len(), 1);
+        assert_eq!(retried[0].0, "b");
+        assert_eq!(shard_of(&retried[0].1).shard_index, shard.shard_index);
+
+        // 반납은 고장이 아니다: 실패 횟수, 운영자 부스트는 그대로
+        let a = sched.nodes.get("a").unwrap();
+        assert_eq!((a.failure_count, a.pending_reservations), (0, 0));
+        assert_eq!(a.reserved_at, None);
+        drop(a);
+        assert_eq!(sched.priority_boosts.get("a").unwrap().multiplier, 1.2);
+        assert!(sched.rejection_penalties.contains_key("a"));
+        assert_eq!(sched.find_smart_candidates()[0], "b");
+
+        clock.advance_secs(SchedulerConfig::default().rejection_penalty_secs + 1.0);
+        sched.release_reservation("b");
+        assert_eq!(sched.find_smart_candidates()[0], "a");
+    }
+
+    #[test]
+    fn legacy_state_upgrades_and_newer_state_is_refused() {
+        // 버전 필드가 생기기 전 포맷: schema_version 및 이후 추가된 필드가 없다
+        let legacy = serde_json::json!({
+            "saved_at_unix_secs": 1_700_000_000u64,
+            "master_id": "master-old",
+            "nodes": [{
+                "node_id": "legacy",
+                "device_model": "old-rig",
+                "cpu_cores": 8,
+                "total_ram_mb": 16384,
+                "has_npu": false,
+                "has_cuda": true,
+                "has_rocm": false,
+                "has_intel_arc": false,
+                "pcie_lanes": 16,
+                "pcie_gen": 3,
+                "memory_bandwidth_gbps": 200.0,
+                "compute_units": 40,
+                "current_tier": "Tier2Standard",
+                "last_seen_age_secs": 2.0,
+                "cpu_load": 0.2,
+                "gpu_load": 0.3,
+                "is_charging": true,
+                "network_type": "ethernet",
+                "user_allowed": true,
+                "net_rtt_ema_ms": 12.0,
+                "health_state": "Healthy",
+                "failure_count": 0,
+                "is_quarantined": false
+            }]
+        });
+        let bytes = serde_json::to_vec(&legacy).unwrap();
+
+        assert_eq!(STATE_SCHEMA_VERSION, 2);
+        let state = StcScheduler::decode_state(&bytes).unwrap();
+        assert_eq!(state.schema_version, 2);
+        let restored = &state.nodes[0];
+        assert_eq!(restored.zone, None);
+        assert_eq!(restored.total_vram_gb, 0);
+        assert!(restored.cached_models.is_empty() && !restored.is_draining);
+        assert_eq!(restored.failure_domain, None);
+        assert_eq!(restored.last_effective_opi, 0.0);
+
+        // 명시적인 v1 파일도 같은 마이그레이션을 거친다
+        let mut explicit_v1 = legacy.clone();
+        explicit_v1["schema_version"] = 1.into();
+        let upgraded =
+            StcScheduler::decode_state(&serde_json::to_vec(&explicit_v1).unwrap()).unwrap();
+        assert_eq!(upgraded.schema_version, 2);
+
+        // v2부터는 마이그레이션 없이 필드가 모두 있어야 한다
+        let mut broken_v2 = legacy.clone();
+        broken_v2["schema_version"] = 2.into();
+        assert!(matches!(
+            StcScheduler::decode_state(&serde_json::to_vec(&broken_v2).unwrap()),
+            Err(StateError::Parse(_))
+        ));
+
+        let sched = scheduler(SchedulerConfig::default());
+        assert_eq!(sched.import_state(&bytes).unwrap(), 1);
+        assert_eq!(sched.nodes.get("legacy").unwrap().net_rtt_ema_ms, 12.0);
+
+        let mut newer = legacy;
+        newer["schema_version"] = (STATE_SCHEMA_VERSION + 1).into();
+        assert!(matches!(
+            StcScheduler::decode_state(&serde_json::to_vec(&newer).unwrap()),
+            Err(StateError::UnsupportedVersion { found, supported })
+                if found == STATE_SCHEMA_VERSION + 1 && supported == STATE_SCHEMA_VERSION
+        ));
+    }
+
+    #[test]
+    fn reconcile_repairs_and_reports_injected_drift() {
+        let clock = MockClock::new();
+        let sched = scheduler_with(SchedulerConfig::default(), clock.clone());
+        sched.register_node_ctx(node("a")).unwrap();
+        let shard_id = |cmds: &[(String, ServerCommand)]| shard_of(&cmds[0].1).shard_id.clone();
+        let dangling = sched.shard_task("worker", 1);
+        sched
+            .handle_ack(&ack(&dangling[0].0, &dangling[0].1, true))
+            .unwrap();
+        let stale = sched.shard_task("worker", 1);
+        clock.advance_secs(SchedulerConfig::default().pending_ack_timeout_secs + 1.0);
+        let fresh = sched.shard_task("worker", 1);
+
+        // 제거 경로를 거치지 않고 사라진 노드를 흉내낸다
+        for a in sched.assignments.write().values_mut() {
+            if a.shard_id == shard_id(&dangling) {
+                a.node_id = "vanished".to_string();
+            }
+        }
+        sched.priority_boosts.insert(
+            "vanished".to_string(),
+            PriorityBoost {
+                multiplier: 2.0,
+                expires_at: None,
+            },
+        );
+        sched.replica_sets.insert(
+            "worker".to_string(),
+            vec!["a".to_string(), "vanished".to_string()],
+        );
+        sched.node_index.write().push("vanished".to_string());
+
+        let report = sched.reconcile();
+        assert_eq!(report.dangling_assignments, vec![shard_id(&dangling)]);
+        assert_eq!(report.expired_pending, vec![shard_id(&stale)]);
+        assert_eq!(
+            report.pruned_replica_members,
+            vec![("worker".to_string(), "vanished".to_string())]
+        );
+        assert_eq!(report.orphaned_side_entries, 1);
+
+        assert_eq!(*sched.node_index.read(), vec!["a".to_string()]);
+        assert_eq!(
+            *sched.replica_sets.get("worker").unwrap(),
+            vec!["a".to_string()]
+        );
+        let remaining: Vec<String> = sched
+            .all_assignments()
+            .into_iter()
+            .map(|r| r.shard_id)
+            .collect();
+        assert_eq!(remaining, vec![shard_id(&fresh)]);
+        assert!(sched.reconcile().is_clean());
+    }
+
+    #[test]
+    fn a_single_dip_does_not_demote_but_a_sustained_one_does() {
+        let sched = scheduler(SchedulerConfig {
+            capability_regression_ratio: 0.0,
+            ..Default::default()
+        });
+        let mut small = node("dippy");
+        small.total_ram_mb = 8 * 1024;
+        small.pcie_lanes = 4;
+        small.pcie_gen = 3;
+        small.memory_bandwidth_gbps = 100.0;
+        small.compute_units = 400;
+        sched.register_node_ctx(small).unwrap();
+        let tier = || sched.nodes.get("dippy").unwrap().current_tier.clone();
+        let report = |cu: u32| sched.update_node_capabilities("dippy", 100.0, cu).unwrap();
+
+        for _ in 0..20 {
+            report(400);
+        }
+        assert_eq!(tier(), NodeTier::Tier1HighPerformance);
+        let steady = sched.smoothed_tier_score("dippy").unwrap();
+
+        report(10);
+        assert_eq!(tier(), NodeTier::Tier1HighPerformance);
+        assert!(sched.smoothed_tier_score("dippy").unwrap() < steady);
+        report(400);
+        assert_eq!(tier(), NodeTier::Tier1HighPerformance);
+
+        for _ in 0..5 {
+            report(10);
+        }
+        assert_eq!(tier(), NodeTier::Tier2Standard);
+    }
+
+    #[test]
+    fn duplicate_container_ids_follow_the_policy() {
+        let mut first = container("vision");
+        first.description = "first".to_string();
+        let mut last = container("vision");
+        last.description = "last".to_string();
+        let path = std::env::temp_dir().join(format!("stc-dup-{}.json", std::process::id()));
+        let file = vec![first, container("speech"), last];
+        fs::write(&path, serde_json::to_vec(&file).unwrap()).unwrap();
+
+        let load = |policy: DuplicateContainerPolicy| {
+            let sched = StcScheduler::builder("master-test", thresholds())
+                .with_config(SchedulerConfig {
+                    duplicate_container_policy: policy,
+                    ..Default::default()
+                })
+                .with_clock(MockClock::new())
+                .with_container_path(path.to_str().unwrap())
+                .build()
+                .unwrap();
+            let description = sched.get_container("vision").map(|c| c.description);
+            let reloaded = sched.reload_containers(path.to_str().unwrap());
+            let registered = sched.register_containers(vec![container("speech")]);
+            (description, reloaded, registered)
+        };
+
+        let (description, reloaded, registered) = load(DuplicateContainerPolicy::RejectAll);
+        assert_eq!(description, None);
+        assert!(reloaded.unwrap_err().contains("vision"));
+        assert!(registered.is_ok());
+
+        let (description, reloaded, registered) = load(DuplicateContainerPolicy::KeepFirst);
+        assert_eq!(description.as_deref(), Some("first"));
+        assert_eq!(reloaded, Ok(2));
+        assert_eq!(registered, Ok(2));
+
+        let (description, reloaded, _) = load(DuplicateContainerPolicy::KeepLast);
+        assert_eq!(description.as_deref(), Some("last"));
+        assert_eq!(reloaded, Ok(2));
+
+        let sched = scheduler(SchedulerConfig {
+            duplicate_container_policy: DuplicateContainerPolicy::RejectAll,
+            ..Default::default()
+        });
+        sched
+            .register_containers(vec![container("speech")])
+            .unwrap();
+        assert_eq!(
+            sched.register_containers(vec![container("speech")]),
+            Err(ContainerRegistryError::DuplicateIds(vec![
+                "speech".to_string()
+            ]))
+        );
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn replace_nodes_keeps_overlapping_ema_and_swaps_the_rest() {
+        let sched = scheduler(SchedulerConfig::default());
+        sched.register_node_ctx(node("kept")).unwrap();
+        sched.register_node_ctx(node("dropped")).unwrap();
+        heartbeat(&sched, "kept", 0.2, 0.2, 40.0);
+        heartbeat(&sched, "kept", 0.2, 0.2, 60.0);
+        let ema = sched.nodes.get("kept").unwrap().net_rtt_ema_ms;
+        assert!(ema > 40.0);
+        let placed = sched.shard_task("worker", 2);
+        let on_dropped = placed.iter().filter(|(id, _)| id == "dropped").count();
+
+        let mut upgraded = node("kept");
+        upgraded.compute_units = 80;
+        let report = sched.replace_nodes(vec![upgraded, node("added")]);
+
+        assert_eq!(report.added, vec!["added".to_string()]);
+        assert_eq!(report.removed, vec!["dropped".to_string()]);
+        assert_eq!(report.retained, vec!["kept".to_string()]);
+        assert_eq!(report.orphaned.len(), on_dropped);
+        let kept = sched.nodes.get("kept").unwrap();
+        assert_eq!((kept.net_rtt_ema_ms, kept.compute_units), (ema, 80));
+        drop(kept);
+        assert_eq!(sched.nodes.get("added").unwrap().net_rtt_ema_ms, 0.0);
+        assert!(!sched.nodes.contains_key("dropped"));
+        assert_eq!(sched.node_index.read().len(), 2);
+    }
+
+    #[test]
+    fn backward_heartbeat_timestamps_leave_health_on_arrival_time() {
+        let clock = MockClock::new();
+        let sched = scheduler_with(SchedulerConfig::default(), clock.clone());
+        sched.register_node_ctx(node("drifty")).unwrap();
+        heartbeat(&sched, "drifty", 0.1, 0.1, 5.0);
+
+        assert!(sched.observe_heartbeat_timestamp("drifty", 10_000_000));
+        // NTP 보정으로 한 시간 뒤로 점프한 뒤에도 Heartbeat는 계속 도착한다
+        for i in 0..5u64 {
+            clock.advance_secs(5.0);
+            let monotonic = sched.observe_heartbeat_timestamp("drifty", 10_000_000 - 3_600_000 + i);
+            assert_eq!(monotonic, i > 0);
+            heartbeat(&sched, "drifty", 0.1, 0.1, 5.0);
+        }
+        assert_eq!(sched.node_stats.get("drifty").unwrap().clock_regressions, 1);
+        assert_eq!(sched.reevaluate_node("drifty"), Some(HealthState::Healthy));
+
+        // 노드 시계와 무관하게 도착이 끊기면 그대로 타임아웃
+        clock.advance_secs(sched.health_thresholds.quarantine_after_secs + 1.0);
+        assert!(!sched.observe_heartbeat_timestamp("drifty", 0));
+        assert_eq!(
+            sched.reevaluate_node("drifty"),
+            Some(HealthState::Quarantined)
+        );
+        assert!(!sched.observe_heartbeat_timestamp("ghost", 0));
+    }
+
+    #[test]
+    fn npu_node_wins_only_its_favored_domain() {
+        let sched = scheduler(SchedulerConfig {
+            accelerator_affinity: vec![AcceleratorAffinity {
+                domain: "babel".to_string(),
+                accelerator: AcceleratorKind::Npu,
+                multiplier: 2.0,
+            }],
+            ..Default::default()
+        });
+        let mut npu = node("npu");
+        npu.has_cuda = false;
+        npu.has_npu = true;
+        sched.register_node_ctx(npu).unwrap();
+        sched.register_node_ctx(node("cuda")).unwrap();
+        let mut babel = container("translate");
+        babel.domain = "Babel".to_string();
+        let mut training = container("train");
+        training.domain = "training".to_string();
+        sched.register_containers(vec![babel, training]).unwrap();
+
+        assert_eq!(
+            sched.best_node_for_container("translate").unwrap().node_id,
+            "npu"
+        );
+        assert_eq!(
+            sched.best_node_for_container("train").unwrap().node_id,
+            "cuda"
+        );
+    }
+
+    #[test]
+    fn state_writes_retry_then_fall_back() {
+        // 처음 fail_first번은 실패하는 저장소
+        #[derive(Debug, Clone, Default)]
+        struct FlakyStore {
+            fail_first: u32,
+            calls: Arc<Mutex<u32>>,
+            saved: Arc<Mutex<Option<Vec<u8>>>>,
+        }
+        impl StateStore for FlakyStore {
+            fn save(&self, bytes: &[u8]) -> io::Result<()> {
+                let mut calls = self.calls.lock();
+                *calls += 1;
+                if *calls <= self.fail_first {
+                    return Err(io::Error::other("disk full"));
+                }
+                *self.saved.lock() = Some(bytes.to_vec());
+                Ok(())
+            }
+            fn load(&self) -> Option<Vec<u8>> {
+                self.saved.lock().clone()
+            }
+        }
+
+        let fallback_dir =
+            std::env::temp_dir().join(format!("stc-fallback-{}", std::process::id()));
+        fs::create_dir_all(&fallback_dir).unwrap();
+        let build = |store: FlakyStore| {
+            let sched = StcScheduler::builder("master-test", thresholds())
+                .with_config(SchedulerConfig {
+                    state_write_attempts: 3,
+                    state_write_backoff_ms: 1,
+                    state_fallback_dir: Some(fallback_dir.clone()),
+                    ..Default::default()
+                })
+                .with_clock(MockClock::new())
+                .with_container_path("/nonexistent/stc-test-containers.json")
+                .with_state_store(store)
+                .build()
+                .unwrap();
+            sched.register_node_ctx(node("a")).unwrap();
+            sched
+        };
+
+        let recovering = FlakyStore {
+            fail_first: 2,
+            ..Default::default()
+        };
+        let sched = build(recovering.clone());
+        sched.save_state_to_store().unwrap();
+        assert_eq!(*recovering.calls.lock(), 3);
+        let restarted = build(recovering);
+        restarted.deregister_node("a");
+        assert_eq!(restarted.load_state_from_store().unwrap(), 1);
+
+        let broken = FlakyStore {
+            fail_first: u32::MAX,
+            ..Default::default()
+        };
+        match build(broken.clone()).save_state_to_store() {
+            Err(StateError::WrittenToFallback { fallback, cause }) => {
+                assert_eq!(cause.to_string(), "disk full");
+                let bytes = fs::read(&fallback).unwrap();
+                assert_eq!(StcScheduler::decode_state(&bytes).unwrap().nodes.len(), 1);
+            }
+            other => panic!("expected fallback write, got {other:?}"),
+        }
+        assert_eq!(*broken.calls.lock(), 3);
+        assert!(broken.saved.lock().is_none());
+        let _ = fs::remove_dir_all(fallback_dir);
     }
 
-    pub fn handle_babel_request(
-        &self,
-        client_id: &str,
-        _req: &stc::BabelRequestPayload,
-    ) -> Option<ServerCommand> {
-        info!("[Babel] Session start: client={}", client_id);
+    #[test]
+    fn limiting_factor_names_network_or_load() {
+        let sched = scheduler(SchedulerConfig::default());
+        for id in ["far", "busy", "down"] {
+            sched.register_node_ctx(node(id)).unwrap();
+        }
+        heartbeat(&sched, "far", 0.1, 0.1, 400.0);
+        heartbeat(&sched, "busy", 0.75, 0.75, 2.0);
+        quarantine(&sched, "down");
 
-        Some(ServerCommand {
-            r#type: ServerCmdType::StreamInit as i32,
-            task_id: format!("babel_{}", client_id),
-            payload: None,
-        })
+        assert_eq!(sched.limiting_factor("far"), Some(LimitingFactor::Network));
+        assert_eq!(sched.limiting_factor("busy"), Some(LimitingFactor::Load));
+        assert_eq!(sched.limiting_factor("down"), Some(LimitingFactor::Health));
+        assert_eq!(
+            sched.get_node("far").unwrap().limiting_factor,
+            LimitingFactor::Network
+        );
+        assert_eq!(sched.limiting_factor("ghost"), None);
     }
 
-    pub fn handle_assist_request(
-        &self,
-        _client_id: &str,
-        _req: &stc::AssistRequestPayload,
-    ) -> Option<ServerCommand> {
-        None
+    #[test]
+    fn communicating_container_is_drawn_to_its_peers_zone() {
+        let sched = scheduler(SchedulerConfig::default());
+        let mut east = node("east");
+        east.zone = Some("east".to_string());
+        east.compute_units = 64;
+        let mut west = node("west");
+        west.zone = Some("west".to_string());
+        sched.register_node_ctx(east).unwrap();
+        sched.register_node_ctx(west).unwrap();
+        sched
+            .replica_sets
+            .insert("db".to_string(), vec!["west".to_string()]);
+        let mut api = container("api");
+        api.communicates_with = vec!["db".to_string()];
+        sched
+            .register_containers(vec![api, container("loner")])
+            .unwrap();
+
+        assert_eq!(
+            sched.best_node_for_container("loner").unwrap().node_id,
+            "east"
+        );
+        assert_eq!(
+            sched.best_node_for_container("api").unwrap().node_id,
+            "west"
+        );
     }
 
-    pub fn handle_admin_action(&self, req: &AdminRequestPayload) -> Result<String, String> {
-        info!(
-            "[Admin] Action: {} target={} msg={}",
-            req.action, req.target, req.message
+    #[test]
+    fn out_of_range_master_status_is_clamped_without_spurious_sharding() {
+        let sched = scheduler(SchedulerConfig::default());
+        sched.register_node_ctx(node("a")).unwrap();
+        let stored = || {
+            let s = sched.server_status.lock();
+            (s.cpu_load, s.gpu_load, s.vram_usage_ratio)
+        };
+
+        assert!(sched.update_master_status(-3.0, -0.5, -1.5));
+        assert_eq!(stored(), (0.0, 0.0, 0.0));
+        let outcome = sched.check_server_overload_and_shard();
+        assert!(outcome.tripped.is_empty() && outcome.commands.is_empty());
+
+        assert!(!sched.update_master_status(f64::NAN, 0.1, 0.1));
+        assert_eq!(stored(), (0.0, 0.0, 0.0));
+        assert!(sched.check_server_overload_and_shard().commands.is_empty());
+
+        assert!(sched.update_master_status(0.2, 7.0, 0.1));
+        assert_eq!(stored(), (0.2, 1.0, 0.1));
+
+        let lenient = scheduler(SchedulerConfig {
+            reject_nan_master_status: false,
+            ..Default::default()
+        });
+        assert!(lenient.update_master_status(f64::NAN, f64::INFINITY, 0.5));
+        let s = lenient.server_status.lock();
+        assert_eq!((s.cpu_load, s.gpu_load), (0.0, 1.0));
+    }
+
+    #[test]
+    fn in_memory_state_store_round_trips() {
+        #[derive(Debug, Clone, Default)]
+        struct MemoryStore(Arc<Mutex<Option<Vec<u8>>>>);
+        impl StateStore for MemoryStore {
+            fn save(&self, bytes: &[u8]) -> io::Result<()> {
+                *self.0.lock() = Some(bytes.to_vec());
+                Ok(())
+            }
+            fn load(&self) -> Option<Vec<u8>> {
+                self.0.lock().clone()
+            }
+        }
+
+        let store = MemoryStore::default();
+        let build = || {
+            StcScheduler::builder("master-test", thresholds())
+                .with_clock(MockClock::new())
+                .with_container_path("/nonexistent/stc-test-containers.json")
+                .with_state_store(store.clone())
+                .build()
+                .unwrap()
+        };
+
+        let first = build();
+        assert_eq!(first.load_state_from_store().unwrap(), 0);
+        let mut zoned = node("zoned");
+        zoned.zone = Some("east".to_string());
+        first.register_node_ctx(zoned).unwrap();
+        first.register_node_ctx(node("plain")).unwrap();
+        heartbeat(&first, "plain", 0.3, 0.4, 25.0);
+        first.save_state_to_store().unwrap();
+
+        let second = build();
+        assert_eq!(second.load_state_from_store().unwrap(), 2);
+        let plain = second.nodes.get("plain").unwrap();
+        assert_eq!((plain.cpu_load, plain.gpu_load), (0.3, 0.4));
+        assert_eq!(plain.net_rtt_ema_ms, 25.0);
+        drop(plain);
+        assert_eq!(
+            second.nodes.get("zoned").unwrap().zone.as_deref(),
+            Some("east")
+        );
+    }
+
+    #[test]
+    fn suspect_flapping_escalates_to_quarantine() {
+        let run = |chronic_suspect_quarantine: bool| {
+            let clock = MockClock::new();
+            let sched = scheduler_with(
+                SchedulerConfig {
+                    chronic_suspect_quarantine,
+                    ..Default::default()
+                },
+                clock.clone(),
+            );
+            sched.register_node_ctx(node("flappy")).unwrap();
+            // Heartbeat 간격이 Suspect 경계(10s)와 격리 경계(30s) 사이를 오간다
+            let mut states = Vec::new();
+            for _ in 0..12 {
+                clock.advance_secs(12.0);
+                sched.reevaluate_node("flappy");
+                clock.advance_secs(12.0);
+                heartbeat(&sched, "flappy", 0.1, 0.1, 5.0);
+                states.push(sched.nodes.get("flappy").unwrap().health_state);
+            }
+            (states, sched.flaky_nodes())
+        };
+
+        let (states, flaky) = run(true);
+        assert_eq!(states[0], HealthState::Healthy);
+        assert_eq!(states.last(), Some(&HealthState::Quarantined));
+        assert_eq!(flaky, vec!["flappy".to_string()]);
+
+        let (states, flaky) = run(false);
+        assert!(states.iter().all(|s| *s == HealthState::Healthy));
+        assert_eq!(flaky, vec!["flappy".to_string()]);
+    }
+
+    #[test]
+    fn sharding_status_explains_why_nothing_fires() {
+        let sched = scheduler(SchedulerConfig::default());
+        let mut opted_out = node("opted-out");
+        opted_out.user_allowed = false;
+        sched.register_node_ctx(opted_out).unwrap();
+
+        sched.update_master_status(0.3, 0.2, 0.1);
+        let status = sched.sharding_status();
+        assert_eq!(status.blocked, Some(ShardingBlock::BelowThreshold));
+        assert!(status.trigger.is_none());
+        assert_eq!(status.cpu_load, 0.3);
+
+        sched.update_master_status(0.95, 0.2, 0.1);
+        let status = sched.sharding_status();
+        assert!(status.trigger.is_some());
+        assert_eq!(status.eligible_candidates, 0);
+        assert_eq!(status.blocked, Some(ShardingBlock::NoCandidates));
+        assert!(sched.check_server_overload_and_shard().commands.is_empty());
+
+        sched.register_node_ctx(node("ready")).unwrap();
+        let status = sched.sharding_status();
+        assert_eq!((status.eligible_candidates, status.blocked), (1, None));
+    }
+
+    #[test]
+    fn client_affinity_sticks_until_the_node_is_saturated() {
+        let sched = scheduler(SchedulerConfig::default());
+        sched.register_node_ctx(node("a")).unwrap();
+        sched.register_node_ctx(node("b")).unwrap();
+        let (first, _) = sched
+            .handle_offload_request("client-1", &offload_request("worker"))
+            .unwrap();
+        let other = if first == "a" { "b" } else { "a" };
+
+        // 예약 때문에 순위는 다른 노드가 앞서도 같은 클라이언트는 이전 노드로
+        assert_eq!(sched.find_smart_candidates()[0], other);
+        let (second, _) = sched
+            .handle_offload_request("client-1", &offload_request("worker"))
+            .unwrap();
+        assert_eq!(second, first);
+
+        heartbeat(&sched, &first, 1.0, 1.0, 5.0);
+        let (third, _) = sched
+            .handle_offload_request("client-1", &offload_request("worker"))
+            .unwrap();
+        assert_eq!(third, other);
+        assert_eq!(
+            sched.client_affinity.get("client-1").unwrap().node_id,
+            other
+        );
+    }
+
+    #[test]
+    fn concurrent_heartbeats_and_reconcile_neither_deadlock_nor_tear() {
+        let sched = Arc::new(scheduler(SchedulerConfig::default()));
+        for id in ["shared", "side-0", "side-1", "side-2", "side-3"] {
+            sched.register_node_ctx(node(id)).unwrap();
+        }
+        let expected_tier = sched.nodes.get("shared").unwrap().current_tier.clone();
+
+        let (done_tx, done_rx) = mpsc::channel();
+        let worker = Arc::clone(&sched);
+        std::thread::spawn(move || {
+            std::thread::scope(|s| {
+                // 같은 node_id에 8개 스레드가 동시에 Heartbeat (cpu == gpu로 보내 찢어진 갱신 검출)
+                for t in 0..8 {
+                    let sched = &worker;
+                    s.spawn(move || {
+                        for i in 0..500 {
+                            let load = ((t * 500 + i) % 70) as f64 / 100.0;
+                            heartbeat(sched, "shared", load, load, 20.0);
+                        }
+                    });
+                }
+                for t in 0..4 {
+                    let sched = &worker;
+                    s.spawn(move || {
+                        for _ in 0..500 {
+                            heartbeat(sched, &format!("side-{t}"), 0.2, 0.2, 10.0);
+                        }
+                    });
+                }
+                for _ in 0..2 {
+                    let sched = &worker;
+                    s.spawn(move || {
+                        for _ in 0..200 {
+                            assert!(sched.reconcile().is_clean());
+                            sched.tick();
+                        }
+                    });
+                }
+            });
+            done_tx.send(()).unwrap();
+        });
+        done_rx
+            .recv_timeout(Duration::from_secs(60))
+            .expect("heartbeat/reconcile threads deadlocked");
+
+        assert_eq!(sched.nodes.len(), 5);
+        assert_eq!(sched.node_index.read().len(), 5);
+        let shared = sched.nodes.get("shared").unwrap();
+        assert_eq!(shared.cpu_load, shared.gpu_load);
+        assert!((shared.net_rtt_ema_ms - 20.0).abs() < 1e-9);
+        assert_eq!(shared.health_state, HealthState::Healthy);
+        assert_eq!(shared.current_tier, expected_tier);
+        drop(shared);
+        let history = sched.node_load_history("shared");
+        assert_eq!(
+            history.len(),
+            SchedulerConfig::default().load_history_capacity
+        );
+        assert!(history.iter().all(|s| s.cpu_load == s.gpu_load));
+    }
+
+    #[test]
+    fn a_domain_threshold_sheds_earlier_than_the_global_one() {
+        let vram_heavy = OverloadThresholds {
+            cpu_max: 0.5,
+            gpu_max: 0.5,
+            vram_pressure_max: 0.6,
+        };
+        let sched = scheduler(SchedulerConfig {
+            domain_thresholds: HashMap::from([("diffusion".to_string(), vram_heavy)]),
+            ..Default::default()
+        });
+        sched.register_node_ctx(node("a")).unwrap();
+        sched.update_master_status(0.6, 0.3, 0.7);
+
+        assert!(sched.check_server_overload_and_shard().tripped.is_empty());
+        sched.set_master_load_domain(Some("llm".to_string()));
+        assert!(sched.check_server_overload_and_shard().tripped.is_empty());
+
+        sched.set_master_load_domain(Some("diffusion".to_string()));
+        let outcome = sched.check_server_overload_and_shard();
+        assert!(!outcome.tripped.is_empty());
+        assert!(!outcome.commands.is_empty());
+        assert_eq!(sched.sharding_status().thresholds.cpu_max, 0.5);
+    }
+
+    #[test]
+    fn uptime_bonus_grows_to_the_cap_and_resets_on_quarantine() {
+        let clock = MockClock::new();
+        let sched = scheduler_with(
+            SchedulerConfig {
+                reliability_bonus_max: 0.2,
+                reliability_full_uptime_secs: 100.0,
+                ..Default::default()
+            },
+            clock.clone(),
+        );
+        sched.register_node_ctx(node("steady")).unwrap();
+        heartbeat(&sched, "steady", 0.1, 0.1, 5.0);
+        let run_for = |secs: u32| {
+            for _ in 0..secs / 5 {
+                clock.advance_secs(5.0);
+                heartbeat(&sched, "steady", 0.1, 0.1, 5.0);
+            }
+            sched.effective_opi("steady").unwrap()
+        };
+
+        let quarter = run_for(25);
+        let half = run_for(25);
+        let full = run_for(50);
+        let later = run_for(100);
+        assert!(quarter < half && half < full);
+        // 가동 시간 외 항목이 같으면 점수 비율은 보너스 비율 그대로
+        assert!((full / half - 1.2 / 1.1).abs() < 1e-9);
+        assert_eq!(later, full);
+        assert_eq!(sched.get_node("steady").unwrap().healthy_uptime_secs, 200.0);
+
+        quarantine(&sched, "steady");
+        assert_eq!(sched.healthy_uptime_secs("steady"), Some(0.0));
+    }
+
+    #[test]
+    fn top_nodes_orders_and_truncates() {
+        let sched = scheduler(SchedulerConfig::default());
+        assert!(sched.top_nodes(5).is_empty());
+
+        for (id, compute_units) in [("mid", 60), ("low", 20), ("high", 120)] {
+            let mut n = node(id);
+            n.compute_units = compute_units;
+            sched.register_node_ctx(n).unwrap();
+        }
+        let mut blocked = node("blocked");
+        blocked.user_allowed = false;
+        sched.register_node_ctx(blocked).unwrap();
+
+        let ids = |n: usize| -> Vec<String> {
+            sched.top_nodes(n).into_iter().map(|(id, _)| id).collect()
+        };
+        assert_eq!(ids(2), vec!["high", "mid"]);
+        assert_eq!(ids(10), vec!["high", "mid", "low"]);
+        assert!(ids(0).is_empty());
+        let top = sched.top_nodes(3);
+        assert!(top.windows(2).all(|w| w[0].1 >= w[1].1));
+        assert_eq!(top[0].1, sched.effective_opi("high").unwrap());
+    }
+
+    #[test]
+    fn impossible_requirements_are_infeasible_and_busy_fleets_retryable() {
+        let sched = scheduler(SchedulerConfig::default());
+        sched.register_node_ctx(node("only")).unwrap();
+        let mut arc = container("arc-only");
+        arc.required_accelerator = AcceleratorKind::IntelArc;
+        let mut cuda = container("cuda");
+        cuda.required_accelerator = AcceleratorKind::Cuda;
+        sched.register_containers(vec![arc, cuda]).unwrap();
+
+        let impossible = sched.place_container("arc-only").unwrap_err();
+        assert!(!impossible.is_retryable());
+        assert!(matches!(
+            &impossible,
+            PlacementError::Infeasible { container_id, reason }
+                if container_id == "arc-only" && reason.contains("IntelArc")
+        ));
+        assert_eq!(sched.place_container("cuda"), Ok("only".to_string()));
+
+        quarantine(&sched, "only");
+        let busy = sched.place_container("cuda").unwrap_err();
+        assert_eq!(busy, PlacementError::NoCapacity("cuda".to_string()));
+        assert!(busy.is_retryable());
+        assert!(!sched.place_container("nope").unwrap_err().is_retryable());
+    }
+
+    #[test]
+    fn task_completes_once_every_shard_is_acked_and_finished() {
+        let sched = scheduler(SchedulerConfig::default());
+        sched.register_node_ctx(node("a")).unwrap();
+        sched.register_node_ctx(node("b")).unwrap();
+        let commands = sched.shard_task("worker", 3);
+        let task_id = commands[0].1.task_id.clone();
+
+        let sharded: Vec<SchedulerEvent> = sched
+            .drain_events()
+            .into_iter()
+            .filter(|e| matches!(e, SchedulerEvent::TaskSharded { .. }))
+            .collect();
+        assert_eq!(
+            sharded,
+            vec![SchedulerEvent::TaskSharded {
+                task_id: task_id.clone(),
+                shard_count: 3,
+                node_ids: commands.iter().map(|(id, _)| id.clone()).collect(),
+            }]
+        );
+
+        for (node_id, cmd) in &commands {
+            sched.handle_ack(&ack(node_id, cmd, true)).unwrap();
+        }
+        let acked = sched.task_status(&task_id).unwrap();
+        assert_eq!((acked.active, acked.completed), (3, 0));
+        assert!(!acked.is_complete);
+
+        for (node_id, cmd) in &commands[..2] {
+            sched.handle_progress(&progress(node_id, cmd, 1.0)).unwrap();
+        }
+        assert!(!sched.task_status(&task_id).unwrap().is_complete);
+        let (node_id, cmd) = &commands[2];
+        sched.handle_progress(&progress(node_id, cmd, 1.0)).unwrap();
+
+        let done = sched.task_status(&task_id).unwrap();
+        assert_eq!((done.shard_total, done.completed), (3, 3));
+        assert_eq!(done.progress, 1.0);
+        assert!(done.is_complete);
+        assert!(sched.task_status("no-such-task").is_none());
+    }
+
+    #[test]
+    fn consecutive_failures_quarantine_and_a_heartbeat_resets_the_count() {
+        let sched = scheduler(SchedulerConfig::default());
+        sched.register_node_ctx(node("shaky")).unwrap();
+        let max = sched.health_thresholds.max_consecutive_failures;
+
+        for n in 1..max {
+            assert_eq!(sched.record_node_failure("shaky"), Some(n));
+        }
+        heartbeat(&sched, "shaky", 0.1, 0.1, 5.0);
+        let shaky = sched.nodes.get("shaky").unwrap();
+        assert_eq!(
+            (shaky.failure_count, shaky.health_state),
+            (0, HealthState::Healthy)
+        );
+        drop(shaky);
+
+        quarantine(&sched, "shaky");
+        assert!(sched.nodes.get("shaky").unwrap().is_quarantined);
+        assert!(!sched.find_smart_candidates().contains(&"shaky".to_string()));
+        assert_eq!(sched.record_node_failure("ghost"), None);
+    }
+
+    #[test]
+    fn unconverged_rtt_ranks_below_a_proven_low_latency_node() {
+        let sched = scheduler(SchedulerConfig::default());
+        sched.register_node_ctx(node("proven")).unwrap();
+        sched.register_node_ctx(node("newcomer")).unwrap();
+        let samples = SchedulerConfig::default().rtt_min_samples;
+        for _ in 0..samples {
+            heartbeat(&sched, "proven", 0.1, 0.1, 15.0);
+        }
+        heartbeat(&sched, "newcomer", 0.1, 0.1, 2.0);
+        assert_eq!(sched.find_smart_candidates()[0], "proven");
+
+        for _ in 1..samples {
+            heartbeat(&sched, "newcomer", 0.1, 0.1, 2.0);
+        }
+        assert_eq!(sched.find_smart_candidates()[0], "newcomer");
+    }
+
+    #[test]
+    fn premium_refuses_a_degraded_node_that_best_effort_accepts() {
+        let sched = scheduler(SchedulerConfig::default());
+        sched.register_node_ctx(node("laggy")).unwrap();
+        for _ in 0..3 {
+            heartbeat(&sched, "laggy", 0.1, 0.1, 200.0);
+        }
+        assert_eq!(
+            sched.nodes.get("laggy").unwrap().health_state,
+            HealthState::Degraded
+        );
+        let mut premium = container("premium");
+        premium.sla = SlaClass::Premium;
+        let mut best_effort = container("best-effort");
+        best_effort.sla = SlaClass::BestEffort;
+        sched
+            .register_containers(vec![premium, best_effort])
+            .unwrap();
+
+        assert_eq!(
+            sched.place_container("best-effort"),
+            Ok("laggy".to_string())
+        );
+        assert_eq!(
+            sched.place_container("premium"),
+            Err(PlacementError::NoCapacity("premium".to_string()))
+        );
+
+        sched.register_node_ctx(node("healthy")).unwrap();
+        heartbeat(&sched, "healthy", 0.1, 0.1, 5.0);
+        assert_eq!(sched.place_container("premium"), Ok("healthy".to_string()));
+    }
+
+    #[test]
+    fn vram_requirement_keeps_large_models_off_small_gpus() {
+        let sched = scheduler(SchedulerConfig::default());
+        let mut small = node("small-gpu");
+        small.total_vram_gb = 8;
+        small.compute_units = 120;
+        sched.register_node_ctx(small).unwrap();
+        sched.register_node_ctx(node("big-gpu")).unwrap();
+        let mut large = container("large-model");
+        large.required_vram_gb = 20;
+        sched
+            .register_containers(vec![large, container("tiny")])
+            .unwrap();
+
+        assert_eq!(sched.place_container("tiny"), Ok("small-gpu".to_string()));
+        assert_eq!(
+            sched.place_container("large-model"),
+            Ok("big-gpu".to_string())
+        );
+        let placed = sched.shard_task("large-model", 2);
+        assert_eq!(placed.len(), 2);
+        assert!(placed.iter().all(|(id, _)| id == "big-gpu"));
+    }
+
+    #[test]
+    fn mock_clock_drives_health_transitions_and_reevaluation() {
+        let clock = MockClock::new();
+        let sched = scheduler_with(SchedulerConfig::default(), clock.clone());
+        sched.register_node_ctx(node("silent")).unwrap();
+        heartbeat(&sched, "silent", 0.1, 0.1, 5.0);
+        let t = sched.health_thresholds;
+
+        clock.advance_secs(t.suspect_after_secs - 1.0);
+        assert_eq!(sched.reevaluate_node("silent"), Some(HealthState::Healthy));
+        clock.advance_secs(2.0);
+        assert_eq!(sched.reevaluate_node("silent"), Some(HealthState::Suspect));
+        assert!(!sched.nodes.get("silent").unwrap().is_quarantined);
+
+        clock.advance_secs(t.quarantine_after_secs - t.suspect_after_secs);
+        let tick = sched.tick();
+        assert_eq!(
+            tick.health_changes,
+            vec![(
+                "silent".to_string(),
+                HealthState::Suspect,
+                HealthState::Quarantined
+            )]
+        );
+        assert_eq!(
+            sched.nodes.get("silent").unwrap().current_tier,
+            NodeTier::Offline
+        );
+
+        heartbeat(&sched, "silent", 0.1, 0.1, 5.0);
+        let back = sched.nodes.get("silent").unwrap();
+        assert_eq!(back.health_state, HealthState::Healthy);
+        assert_ne!(back.current_tier, NodeTier::Offline);
+        drop(back);
+        assert_eq!(sched.reevaluate_node("ghost"), None);
+    }
+
+    #[test]
+    fn mock_clock_expires_penalties_boosts_and_stale_nodes() {
+        let clock = MockClock::new();
+        let sched = scheduler_with(SchedulerConfig::default(), clock.clone());
+        for id in ["boosted", "steady", "idle"] {
+            sched.register_node_ctx(node(id)).unwrap();
+        }
+        sched.set_priority_boost("boosted", 2.0, Some(clock.now() + Duration::from_secs(30)));
+        let placed = sched.shard_task("worker", 1);
+        let (placed_on, cmd) = &placed[0];
+        assert_eq!(placed_on, "boosted");
+        sched
+            .handle_shard_rejection(&reject(placed_on, cmd, "busy"))
+            .unwrap();
+        let penalty_secs = SchedulerConfig::default().rejection_penalty_secs;
+
+        clock.advance_secs(29.0);
+        for id in ["boosted", "steady"] {
+            heartbeat(&sched, id, 0.1, 0.1, 5.0);
+        }
+        assert!(sched.tick().expired_boosts.is_empty());
+        assert!(sched.rejection_penalties.contains_key("boosted"));
+
+        clock.advance_secs(2.0);
+        assert_eq!(sched.tick().expired_boosts, vec!["boosted".to_string()]);
+        clock.advance_secs(penalty_secs);
+        sched.tick();
+        assert!(sched.rejection_penalties.is_empty());
+
+        for id in ["boosted", "steady"] {
+            heartbeat(&sched, id, 0.1, 0.1, 5.0);
+        }
+        assert_eq!(sched.evict_stale_nodes(60.0), vec!["idle".to_string()]);
+        assert!(sched.evict_stale_nodes(60.0).is_empty());
+        clock.advance_secs(61.0);
+        assert_eq!(sched.evict_stale_nodes(60.0).len(), 2);
+        assert!(sched.nodes.is_empty());
+    }
+
+    #[test]
+    fn charging_mobile_node_outranks_one_on_battery() {
+        let phone = |id: &str| {
+            let mut n = node(id);
+            n.total_ram_mb = 4 * 1024;
+            n.has_cuda = false;
+            n.has_npu = true;
+            n.pcie_lanes = 1;
+            n.pcie_gen = 3;
+            n.memory_bandwidth_gbps = 40.0;
+            n.compute_units = 10;
+            n
+        };
+        let on_battery = |sched: &StcScheduler, id: &str| {
+            sched.update_node_status_with_rtt(
+                id,
+                0.1,
+                0.1,
+                false,
+                "ethernet".to_string(),
+                true,
+                5.0,
+            );
+        };
+        let build = |exclude_mobile_on_battery: bool| {
+            let sched = scheduler(SchedulerConfig {
+                exclude_mobile_on_battery,
+                ..Default::default()
+            });
+            for id in ["plugged", "unplugged"] {
+                sched.register_node_ctx(phone(id)).unwrap();
+            }
+            sched.register_node_ctx(node("desktop")).unwrap();
+            heartbeat(&sched, "plugged", 0.1, 0.1, 5.0);
+            on_battery(&sched, "unplugged");
+            sched
+        };
+
+        let sched = build(false);
+        assert_eq!(
+            sched.nodes.get("unplugged").unwrap().current_tier,
+            NodeTier::Tier3Mobile
+        );
+        assert_eq!(
+            sched.find_smart_candidates(),
+            vec!["desktop", "plugged", "unplugged"]
+        );
+        let ratio =
+            sched.effective_opi("unplugged").unwrap() / sched.effective_opi("plugged").unwrap();
+        assert!((ratio - 0.3).abs() < 1e-9);
+
+        // 배터리 페널티는 모바일 Tier에만 적용
+        heartbeat(&sched, "desktop", 0.1, 0.1, 5.0);
+        let plugged_in = sched.effective_opi("desktop").unwrap();
+        on_battery(&sched, "desktop");
+        assert_eq!(sched.effective_opi("desktop").unwrap(), plugged_in);
+
+        assert_eq!(
+            build(true).find_smart_candidates(),
+            vec!["desktop", "plugged"]
+        );
+    }
+
+    #[test]
+    fn offload_without_a_capable_node_is_rejected() {
+        let sched = scheduler(SchedulerConfig::default());
+        assert_eq!(
+            sched.handle_offload_request("client-1", &offload_request("worker")),
+            Err(PlacementError::NoCapacity("worker".to_string()))
+        );
+
+        sched.register_node_ctx(node("cuda-only")).unwrap();
+        let mut arc = container("arc-model");
+        arc.required_accelerator = AcceleratorKind::IntelArc;
+        sched
+            .register_containers(vec![arc, container("vision")])
+            .unwrap();
+        let err = sched
+            .handle_offload_request("client-1", &offload_request("arc-model"))
+            .unwrap_err();
+        assert!(matches!(err, PlacementError::Infeasible { .. }));
+        assert!(sched.client_sessions.get("client-1").is_none());
+        assert_eq!(
+            sched.nodes.get("cuda-only").unwrap().pending_reservations,
+            0
+        );
+
+        let (node_id, cmd) = sched
+            .handle_offload_request("client-1", &offload_request("vision"))
+            .unwrap();
+        assert_eq!(node_id, "cuda-only");
+        assert_eq!(cmd.r#type, ServerCmdType::OffloadAccepted as i32);
+    }
+
+    #[test]
+    fn shards_avoid_a_second_node_in_the_same_failure_domain() {
+        let sched = scheduler(SchedulerConfig::default());
+        for (id, domain, compute_units) in [
+            ("a1", "rack-a", 120),
+            ("a2", "rack-a", 110),
+            ("b", "rack-b", 40),
+        ] {
+            let mut n = node(id);
+            n.failure_domain = Some(domain.to_string());
+            n.compute_units = compute_units;
+            sched.register_node_ctx(n).unwrap();
+        }
+
+        assert_eq!(sched.find_smart_candidates(), vec!["a1", "b", "a2"]);
+        let mut chosen: Vec<String> = sched
+            .shard_task("worker", 2)
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect();
+        chosen.sort();
+        assert_eq!(chosen, vec!["a1", "b"]);
+
+        // 다른 도메인이 없으면 같은 도메인이라도 사용
+        sched.deregister_node("b");
+        let mut remaining = sched.find_smart_candidates();
+        remaining.sort();
+        assert_eq!(remaining, vec!["a1", "a2"]);
+    }
+
+    #[test]
+    fn nan_scores_sort_without_panicking_and_sink_to_the_bottom() {
+        let sched = scheduler(SchedulerConfig::default());
+        for i in 0..40 {
+            let mut n = node(&format!("n{i:02}"));
+            n.compute_units = 20 + i;
+            sched.register_node_ctx(n).unwrap();
+        }
+        let poisoned = |id: &str| id.ends_with('3') || id.ends_with('7');
+
+        let (ranked, _) = sched.rank_candidates_evaluated(ResourceProfile::Combined, |n| {
+            Some(if poisoned(&n.node_id) { f64::NAN } else { 1.0 })
+        });
+
+        assert_eq!(ranked.len(), 40);
+        let first_nan = ranked.iter().position(|id| poisoned(id)).unwrap();
+        assert_eq!(first_nan, 32);
+        assert!(ranked[first_nan..].iter().all(|id| poisoned(id)));
+        assert_eq!(ranked[0], "n39");
+        // NaN끼리는 tie-break(node_id)로 정렬되어 순서가 고정된다
+        let tail: Vec<&String> = ranked[first_nan..].iter().collect();
+        assert!(tail.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn ethernet_outranks_an_otherwise_identical_cellular_node() {
+        let sched = scheduler(SchedulerConfig::default());
+        for (id, link) in [
+            ("cell", "LTE"),
+            ("wired", "Ethernet"),
+            ("wifi", "wlan"),
+            ("odd", "carrier-pigeon"),
+        ] {
+            sched.register_node_ctx(node(id)).unwrap();
+            sched.update_node_status_with_rtt(id, 0.1, 0.1, true, link.to_string(), true, 5.0);
+        }
+
+        assert_eq!(
+            sched.nodes.get("cell").unwrap().network_kind,
+            NetworkKind::Cellular
+        );
+        assert_eq!(
+            sched.nodes.get("odd").unwrap().network_kind,
+            NetworkKind::Unknown
+        );
+        assert_eq!(
+            sched.find_smart_candidates(),
+            vec!["odd", "wired", "wifi", "cell"]
+        );
+        let opi = |id: &str| sched.effective_opi(id).unwrap();
+        assert!((opi("wired") / opi("cell") - 3.0).abs() < 1e-9);
+        assert_eq!(opi("odd"), opi("wired"));
+    }
+
+    #[test]
+    fn equal_scores_break_ties_by_rtt_then_node_id() {
+        let ranking = |order: &[&str]| {
+            let sched = scheduler(SchedulerConfig::default());
+            for id in order {
+                sched.register_node_ctx(node(id)).unwrap();
+                let rtt = if *id == "zeta" { 3.0 } else { 6.0 };
+                for _ in 0..3 {
+                    heartbeat(&sched, id, 0.1, 0.1, rtt);
+                }
+            }
+            let opi = sched.effective_opi("zeta").unwrap();
+            assert!(order.iter().all(|id| sched.effective_opi(id) == Some(opi)));
+            (sched.find_smart_candidates(), sched.top_nodes(order.len()))
+        };
+
+        let (ranked, top) = ranking(&["beta", "zeta", "alpha", "delta"]);
+        assert_eq!(ranked, vec!["zeta", "alpha", "beta", "delta"]);
+        assert_eq!(
+            top.iter().map(|(id, _)| id.as_str()).collect::<Vec<_>>(),
+            ranked
+        );
+        for order in [
+            ["delta", "alpha", "beta", "zeta"],
+            ["alpha", "beta", "delta", "zeta"],
+        ] {
+            assert_eq!(ranking(&order), (ranked.clone(), top.clone()));
+        }
+    }
+
+    #[test]
+    fn last_resort_revive_works_after_the_whole_fleet_timed_out() {
+        let clock = MockClock::new();
+        let sched = scheduler_with(
+            SchedulerConfig {
+                emergency_revive_last_resort: true,
+                ..Default::default()
+            },
+            clock.clone(),
+        );
+        sched.register_node_ctx(node("early")).unwrap();
+        sched.register_node_ctx(node("late")).unwrap();
+        clock.advance_secs(5.0);
+        heartbeat(&sched, "late", 0.1, 0.1, 5.0);
+        sched.record_node_failure("late");
+        clock.advance_secs(sched.health_thresholds.quarantine_after_secs + 1.0);
+        sched.tick();
+        for id in ["early", "late"] {
+            let n = sched.nodes.get(id).unwrap();
+            assert_eq!(
+                (n.health_state, n.current_tier.clone()),
+                (HealthState::Quarantined, NodeTier::Offline)
+            );
+        }
+        sched.drain_events();
+
+        sched.update_master_status(0.95, 0.5, 0.2);
+        let outcome = sched.check_server_overload_and_shard();
+        assert!(sched.is_capacity_emergency());
+        assert!(!outcome.commands.is_empty());
+        assert!(outcome.commands.iter().all(|(id, _)| id == "late"));
+        let late = sched.nodes.get("late").unwrap();
+        assert_eq!(late.health_state, HealthState::Degraded);
+        assert_ne!(late.current_tier, NodeTier::Offline);
+        assert_eq!(late.failure_count, 0);
+        drop(late);
+        assert!(sched
+            .drain_events()
+            .contains(&SchedulerEvent::HealthChanged {
+                node_id: "late".to_string(),
+                from: HealthState::Quarantined,
+                to: HealthState::Degraded,
+            }));
+
+        // 유예 동안은 sweep이 다시 격리하지 않고, 유예가 끝나도 응답이 없으면 격리
+        clock.advance_secs(SchedulerConfig::default().emergency_revive_grace_secs - 1.0);
+        assert!(sched.tick().health_changes.is_empty());
+        clock.advance_secs(2.0);
+        sched.tick();
+        let late = sched.nodes.get("late").unwrap();
+        assert_eq!(
+            (late.health_state, late.current_tier.clone()),
+            (HealthState::Quarantined, NodeTier::Offline)
+        );
+    }
+
+    #[test]
+    fn fast_path_falls_back_to_a_full_scan_for_rare_capabilities() {
+        let sched = scheduler(SchedulerConfig {
+            fast_path_fleet_threshold: 50,
+            fast_path_sample_size: 8,
+            fast_path_budget_ms: 1_000.0,
+            ..Default::default()
+        });
+        for i in 0..2_000 {
+            sched.register_node_ctx(node(&format!("n{i}"))).unwrap();
+        }
+        let mut arc = node("arc");
+        arc.has_cuda = false;
+        arc.has_intel_arc = true;
+        sched.register_node_ctx(arc).unwrap();
+        let mut rare = container("rare");
+        rare.required_accelerator = AcceleratorKind::IntelArc;
+        sched.register_containers(vec![rare]).unwrap();
+
+        for _ in 0..20 {
+            assert_eq!(sched.place_container("rare"), Ok("arc".to_string()));
+        }
+    }
+
+    // cargo test -- --ignored fast_path_beats_the_exhaustive_scan --nocapture
+    #[test]
+    #[ignore = "50k-node benchmark; slow in debug builds"]
+    fn fast_path_beats_the_exhaustive_scan_on_a_50k_fleet() {
+        let fleet = |fast_path_fleet_threshold: usize| {
+            let sched = scheduler(SchedulerConfig {
+                fast_path_fleet_threshold,
+                fast_path_budget_ms: 1_000.0,
+                ..Default::default()
+            });
+            for i in 0..50_000u32 {
+                let mut n = node(&format!("n{i}"));
+                n.compute_units = 20 + i % 100;
+                sched.register_node_ctx(n).unwrap();
+            }
+            sched
+        };
+        let time = |sched: &StcScheduler| {
+            let started = Instant::now();
+            for _ in 0..20 {
+                assert!(!sched.find_smart_candidates().is_empty());
+            }
+            started.elapsed() / 20
+        };
+
+        let exhaustive = time(&fleet(usize::MAX));
+        let sampled = time(&fleet(SchedulerConfig::default().fast_path_fleet_threshold));
+        println!("50k nodes: exhaustive {exhaustive:?} / fast path {sampled:?} per placement");
+        assert!(sampled * 10 < exhaustive);
+    }
+
+    #[test]
+    fn rtt_less_and_restored_nodes_are_not_treated_as_unconverged() {
+        let sched = scheduler(SchedulerConfig::default());
+        sched.register_node_ctx(node("proven")).unwrap();
+        sched.register_node_ctx(node("legacy")).unwrap();
+        for _ in 0..SchedulerConfig::default().rtt_min_samples {
+            heartbeat(&sched, "proven", 0.1, 0.1, 15.0);
+        }
+        // RTT를 보내지 않는 기존 API 경로: 표본이 영원히 0이어도 페널티를 받지 않는다
+        for _ in 0..5 {
+            sched.update_node_status("legacy", 0.1, 0.1, true, "ethernet".to_string(), true);
+        }
+        let legacy = sched.nodes.get("legacy").unwrap().clone();
+        assert_eq!(sched.calculate_net_factor(&legacy), 1.0);
+        assert_eq!(sched.find_smart_candidates()[0], "legacy");
+
+        heartbeat(&sched, "legacy", 0.1, 0.1, 2.0);
+        let legacy = sched.nodes.get("legacy").unwrap().clone();
+        assert_eq!(
+            sched.calculate_net_factor(&legacy),
+            SchedulerConfig::default().unconverged_net_factor
+        );
+
+        // 복원된 EMA는 수렴한 값으로 취급한다
+        let fast = sched.nodes.get("proven").unwrap().clone();
+        let restored = scheduler(SchedulerConfig::default());
+        restored
+            .import_state(&sched.export_state().unwrap())
+            .unwrap();
+        let proven = restored.nodes.get("proven").unwrap().clone();
+        assert_eq!(
+            restored.calculate_net_factor(&proven),
+            sched.calculate_net_factor(&fast)
+        );
+    }
+
+    #[test]
+    fn rehabilitated_node_passes_probation_back_to_healthy() {
+        let sched = scheduler(SchedulerConfig::default());
+        sched.register_node_ctx(node("a")).unwrap();
+        quarantine(&sched, "a");
+
+        // Heartbeat만으로는 격리가 풀리지 않는다
+        heartbeat(&sched, "a", 0.1, 0.1, 5.0);
+        assert_eq!(
+            sched.nodes.get("a").unwrap().health_state,
+            HealthState::Quarantined
+        );
+
+        assert_eq!(sched.try_rehabilitate_node("a"), Ok(HealthState::Suspect));
+        assert!(!sched.nodes.get("a").unwrap().is_quarantined);
+        assert!(sched.find_smart_candidates().is_empty());
+
+        let clean = sched.config.probation_clean_updates;
+        for _ in 1..clean {
+            heartbeat(&sched, "a", 0.1, 0.1, 5.0);
+            assert_eq!(
+                sched.nodes.get("a").unwrap().health_state,
+                HealthState::Suspect
+            );
+        }
+        heartbeat(&sched, "a", 0.1, 0.1, 5.0);
+        let a = sched.nodes.get("a").unwrap();
+        assert_eq!((a.health_state, a.failure_count), (HealthState::Healthy, 0));
+        drop(a);
+        assert_eq!(sched.find_smart_candidates(), vec!["a".to_string()]);
+    }
+
+    #[test]
+    fn import_keeps_already_registered_nodes_and_their_stats() {
+        let source = scheduler(SchedulerConfig::default());
+        source.register_node_ctx(node("live")).unwrap();
+        source.register_node_ctx(node("saved")).unwrap();
+        heartbeat(&source, "live", 0.9, 0.9, 80.0);
+        let bytes = source.export_state().unwrap();
+
+        let sched = scheduler(SchedulerConfig::default());
+        sched.register_node_ctx(node("live")).unwrap();
+        for _ in 0..2 {
+            heartbeat(&sched, "live", 0.1, 0.1, 5.0);
+        }
+        assert_eq!(sched.import_state(&bytes).unwrap(), 1);
+
+        let live = sched.nodes.get("live").unwrap();
+        assert_eq!(
+            (live.cpu_load, live.health_state),
+            (0.1, HealthState::Healthy)
+        );
+        drop(live);
+        assert_eq!(sched.node_stats.get("live").unwrap().rtt_samples, 2);
+        assert_eq!(sched.node_index.read().len(), 2);
+        assert_eq!(
+            sched.nodes.get("saved").unwrap().health_state,
+            HealthState::Suspect
+        );
+    }
+
+    #[test]
+    fn babel_is_rejected_when_every_measured_node_exceeds_the_rtt_ceiling() {
+        let sched = scheduler(SchedulerConfig::default());
+        let ceiling = sched.config.babel_max_rtt_ms;
+        sched.register_node_ctx(node("far")).unwrap();
+        sched.register_node_ctx(node("farther")).unwrap();
+        heartbeat(&sched, "far", 0.1, 0.1, ceiling * 1.5);
+        heartbeat(&sched, "farther", 0.1, 0.1, ceiling * 3.0);
+        let request = stc::BabelRequestPayload::default();
+
+        assert_eq!(
+            sched.handle_babel_request("client-1", &request),
+            Err(PlacementError::NoCapacity("Babel".to_string()))
+        );
+
+        // RTT가 아직 없는 노드도 측정된 노드가 있는 동안은 대신 쓰지 않는다
+        sched.register_node_ctx(node("unmeasured")).unwrap();
+        sched.update_node_status("unmeasured", 0.1, 0.1, true, "ethernet".to_string(), true);
+        assert!(matches!(
+            sched.handle_babel_request("client-1", &request),
+            Err(PlacementError::NoCapacity(_))
+        ));
+
+        for _ in 0..10 {
+            heartbeat(&sched, "far", 0.1, 0.1, ceiling / 4.0);
+        }
+        let (node_id, _) = sched.handle_babel_request("client-1", &request).unwrap();
+        assert_eq!(node_id, "far");
+    }
+
+    #[test]
+    fn rebalance_needs_the_min_improvement_and_skips_busy_nodes() {
+        let sched = scheduler(SchedulerConfig {
+            rebalance_min_improvement: 1.0,
+            ..Default::default()
+        });
+        let mut slow = node("slow");
+        slow.compute_units = 10;
+        sched.register_node_ctx(slow.clone()).unwrap();
+        let first = sched.shard_task("worker", 1);
+        assert_eq!(first[0].0, "slow");
+        let shard = sched.assignment(&first[0].1.task_id, 0).unwrap();
+
+        // 같은 사양의 유휴 노드는 부하만큼만 나아서 최소 개선 폭에 못 미친다
+        slow.node_id = "peer".to_string();
+        sched.register_node_ctx(slow).unwrap();
+        let threshold = 1.0 + sched.config.rebalance_min_improvement;
+        let (slow_opi, peer_opi) = (
+            sched.effective_opi("slow").unwrap(),
+            sched.effective_opi("peer").unwrap(),
+        );
+        assert!(peer_opi > slow_opi && peer_opi <= slow_opi * threshold);
+        assert!(sched
+            .rebalance_shards(std::slice::from_ref(&shard))
+            .is_empty());
+
+        let mut strong = node("strong");
+        strong.total_ram_mb = 128 * 1024;
+        strong.memory_bandwidth_gbps = 1600.0;
+        strong.compute_units = 200;
+        sched.register_node_ctx(strong).unwrap();
+        let moves = sched.rebalance_shards(std::slice::from_ref(&shard));
+        assert_eq!(moves.len(), 1);
+        let planned = &moves[0];
+        assert_eq!(
+            (planned.from_node.as_str(), planned.to_node.as_str()),
+            ("slow", "strong")
         );
-        Ok("Processed".into())
+        assert_eq!(
+            (planned.task_id.as_str(), planned.shard_index),
+            (shard.task_id.as_str(), 0)
+        );
+        assert!(planned.to_opi > planned.from_opi * threshold);
+
+        // strong이 다른 Shard를 받으면 더 이상 이동 대상이 아니다
+        let second = sched.shard_task("worker", 1);
+        assert_eq!(second[0].0, "strong");
+        assert!(sched
+            .rebalance_shards(std::slice::from_ref(&shard))
+            .is_empty());
+    }
+
+    #[test]
+    fn strict_container_file_fails_the_build_only_for_bad_files() {
+        let dir = std::env::temp_dir().join(format!("stc-strict-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let malformed = dir.join("malformed.json");
+        fs::write(&malformed, b"[{ not json").unwrap();
+        let duplicated = dir.join("duplicated.json");
+        let file = vec![container("vision"), container("vision")];
+        fs::write(&duplicated, serde_json::to_vec(&file).unwrap()).unwrap();
+
+        let build = |path: &Path, strict: bool| {
+            StcScheduler::builder("master-test", thresholds())
+                .with_config(SchedulerConfig {
+                    strict_container_file: strict,
+                    duplicate_container_policy: DuplicateContainerPolicy::RejectAll,
+                    ..Default::default()
+                })
+                .with_clock(MockClock::new())
+                .with_container_path(path.to_str().unwrap())
+                .build()
+        };
+
+        // 파일이 없는 것은 오류가 아니다
+        for strict in [true, false] {
+            let sched = build(&dir.join("missing.json"), strict).unwrap();
+            assert_eq!(sched.container_registry.read().len(), 0);
+        }
+
+        for path in [&malformed, &duplicated] {
+            match build(path, true) {
+                Err(ConfigError::InvalidContainerFile { path: reported, .. }) => {
+                    assert_eq!(reported, path.to_str().unwrap());
+                }
+                Err(other) => panic!("expected InvalidContainerFile, got {other:?}"),
+                Ok(_) => panic!("strict build accepted {}", path.display()),
+            }
+            let lenient = build(path, false).unwrap();
+            assert_eq!(lenient.container_registry.read().len(), 0);
+        }
+        let _ = fs::remove_dir_all(dir);
     }
 }
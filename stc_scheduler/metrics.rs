@@ -0,0 +1,101 @@
+// 스케줄러 내부 지표 (metrics 크레이트 facade). exporter 설치는 임베딩하는 앱 몫
+use super::{HealthState, NodeTier};
+
+const NODES_TOTAL: &str = "scheduler_nodes_total";
+const SHARD_COMMANDS_TOTAL: &str = "scheduler_shard_commands_total";
+const EFFECTIVE_OPI: &str = "scheduler_effective_opi";
+
+pub fn describe() {
+    ::metrics::describe_gauge!(NODES_TOTAL, "Registered nodes by tier and by health state");
+    ::metrics::describe_counter!(SHARD_COMMANDS_TOTAL, "Shard commands issued to nodes");
+    ::metrics::describe_histogram!(EFFECTIVE_OPI, "Effective OPI observed at health evaluation");
+}
+
+fn tier_label(tier: &NodeTier) -> &'static str {
+    match tier {
+        NodeTier::Offline => "offline",
+        NodeTier::Tier3Mobile => "tier3_mobile",
+        NodeTier::Tier2Standard => "tier2_standard",
+        NodeTier::Tier1HighPerformance => "tier1_high_performance",
+    }
+}
+
+fn health_label(health: HealthState) -> &'static str {
+    match health {
+        HealthState::Healthy => "healthy",
+        HealthState::Degraded => "degraded",
+        HealthState::Suspect => "suspect",
+        HealthState::Quarantined => "quarantined",
+    }
+}
+
+// 노드 수 gauge는 전체 재집계 없이 등록/해제/전이 시점에 증감만 한다
+pub fn node_added(tier: &NodeTier, health: HealthState) {
+    ::metrics::gauge!(NODES_TOTAL, "tier" => tier_label(tier)).increment(1.0);
+    ::metrics::gauge!(NODES_TOTAL, "health" => health_label(health)).increment(1.0);
+}
+
+pub fn node_removed(tier: &NodeTier, health: HealthState) {
+    ::metrics::gauge!(NODES_TOTAL, "tier" => tier_label(tier)).decrement(1.0);
+    ::metrics::gauge!(NODES_TOTAL, "health" => health_label(health)).decrement(1.0);
+}
+
+pub fn tier_changed(from: &NodeTier, to: &NodeTier) {
+    if from == to {
+        return;
+    }
+    ::metrics::gauge!(NODES_TOTAL, "tier" => tier_label(from)).decrement(1.0);
+    ::metrics::gauge!(NODES_TOTAL, "tier" => tier_label(to)).increment(1.0);
+}
+
+pub fn health_changed(from: HealthState, to: HealthState) {
+    if from == to {
+        return;
+    }
+    ::metrics::gauge!(NODES_TOTAL, "health" => health_label(from)).decrement(1.0);
+    ::metrics::gauge!(NODES_TOTAL, "health" => health_label(to)).increment(1.0);
+}
+
+pub fn shard_command_issued() {
+    ::metrics::counter!(SHARD_COMMANDS_TOTAL).increment(1);
+}
+
+pub fn observe_effective_opi(score: f64) {
+    ::metrics::histogram!(EFFECTIVE_OPI).record(score);
+}
+
+// 증감 경로를 거치지 않은 상태 변경(관리자 조치 등)으로 생긴 오차를 주기적으로 바로잡는다
+pub fn sync_node_counts<I>(nodes: I)
+where
+    I: IntoIterator<Item = (NodeTier, HealthState)>,
+{
+    let tiers = [
+        NodeTier::Offline,
+        NodeTier::Tier3Mobile,
+        NodeTier::Tier2Standard,
+        NodeTier::Tier1HighPerformance,
+    ];
+    let healths = [
+        HealthState::Healthy,
+        HealthState::Degraded,
+        HealthState::Suspect,
+        HealthState::Quarantined,
+    ];
+    let mut tier_counts = [0u64; 4];
+    let mut health_counts = [0u64; 4];
+    for (tier, health) in nodes {
+        if let Some(i) = tiers.iter().position(|t| *t == tier) {
+            tier_counts[i] += 1;
+        }
+        if let Some(i) = healths.iter().position(|h| *h == health) {
+            health_counts[i] += 1;
+        }
+    }
+
+    for (tier, count) in tiers.iter().zip(tier_counts) {
+        ::metrics::gauge!(NODES_TOTAL, "tier" => tier_label(tier)).set(count as f64);
+    }
+    for (health, count) in healths.into_iter().zip(health_counts) {
+        ::metrics::gauge!(NODES_TOTAL, "health" => health_label(health)).set(count as f64);
+    }
+}